@@ -0,0 +1,254 @@
+//! Generate typed wrappers for every named node in `node-types.json`
+//!
+//! Each node kind becomes a struct around a `tree_sitter::Node`, with a
+//! method per field and a `child()` or `children()` method for the remaining
+//! named children. Sets of child kinds become enums; the hidden grammar rules
+//! they come from (`_value`, `_template_content`, ...) are not recorded in
+//! `node-types.json`, so their names are listed in `SET_NAMES`.
+
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Enum names for child sets, keyed by `kind` or `kind.field`
+const SET_NAMES: &[(&str, &str)] = &[
+    ("template_document", "TemplateContent"),
+    ("member.value", "Value"),
+    ("json5_document", "Json5Value"),
+    ("document", "DocumentBody"),
+    ("mustache_interpolation", "Expression"),
+    ("mustache_section_begin", "TagPart"),
+    ("mustache_section", "SectionChild"),
+    ("mustache_inverted_section", "InvertedSectionChild"),
+    ("name", "NameContent"),
+    ("object", "ObjectContent"),
+];
+
+/// Struct names that would otherwise shadow common types
+const TYPE_NAMES: &[(&str, &str)] = &[("string", "StringLiteral")];
+
+struct Enum {
+    name: String,
+    kinds: BTreeSet<String>,
+}
+
+fn main() {
+    let node_types: Value = serde_json::from_str(tree_sitter_mustache_json5::NODE_TYPES)
+        .expect("node-types.json is valid JSON");
+    let nodes: Vec<&Value> = node_types
+        .as_array()
+        .expect("node-types.json is an array")
+        .iter()
+        .filter(|node| node["named"] == true)
+        .collect();
+
+    // Named sets first, so they win over generated names for the same kinds
+    let mut enums: Vec<Enum> = Vec::new();
+    for (key, _) in SET_NAMES {
+        let (kind, field) = key.split_once('.').unwrap_or((key, ""));
+        let node = nodes
+            .iter()
+            .find(|node| node["type"] == *kind)
+            .unwrap_or_else(|| panic!("no `{kind}` node in node-types.json"));
+        let info = match field {
+            "" => &node["children"],
+            field => &node["fields"][field],
+        };
+        child_type(&mut enums, key, info);
+    }
+
+    let mut structs = String::new();
+    let mut kinds = Vec::new();
+
+    for node in &nodes {
+        let kind = node["type"].as_str().expect("node type");
+        kinds.push(kind.to_string());
+        let name = type_name(kind);
+
+        let _ = write!(
+            structs,
+            "\n/// A `{kind}` node\n\
+             #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n\
+             pub struct {name}<'tree>(Node<'tree>);\n\n\
+             impl<'tree> AstNode<'tree> for {name}<'tree> {{\n    \
+                 fn can_cast(kind: &str) -> bool {{\n        kind == \"{kind}\"\n    }}\n\n    \
+                 fn cast(node: Node<'tree>) -> Option<Self> {{\n        \
+                     Self::can_cast(node.kind()).then_some(Self(node))\n    }}\n\n    \
+                 fn node(&self) -> Node<'tree> {{\n        self.0\n    }}\n}}\n"
+        );
+
+        let mut methods = String::new();
+        if let Some(fields) = node["fields"].as_object() {
+            for (field, info) in fields {
+                let ty = child_type(&mut enums, &format!("{kind}.{field}"), info);
+                let method = method_name(field);
+                if info["multiple"] == true {
+                    let _ = write!(
+                        methods,
+                        "\n    /// Nodes in the `{field}` field\n    \
+                         pub fn {method}(&self) -> Vec<{ty}<'tree>> {{\n        \
+                             let mut cursor = self.0.walk();\n        \
+                             self.0\n            .children_by_field_name(\"{field}\", &mut cursor)\n            \
+                             .filter_map(<{ty}<'tree> as AstNode<'tree>>::cast)\n            .collect()\n    }}\n"
+                    );
+                } else {
+                    let _ = write!(
+                        methods,
+                        "\n    /// The `{field}` field\n    \
+                         pub fn {method}(&self) -> Option<{ty}<'tree>> {{\n        \
+                             self.0\n            .child_by_field_name(\"{field}\")\n            \
+                             .and_then(<{ty}<'tree> as AstNode<'tree>>::cast)\n    }}\n"
+                    );
+                }
+            }
+        }
+        if node["children"].is_object() {
+            let info = &node["children"];
+            let ty = child_type(&mut enums, kind, info);
+            if info["multiple"] == true {
+                let _ = write!(
+                    methods,
+                    "\n    /// Named children outside any field\n    \
+                     pub fn children(&self) -> Vec<{ty}<'tree>> {{\n        \
+                         crate::unnamed_field_children(self.0)\n    }}\n"
+                );
+            } else {
+                let _ = write!(
+                    methods,
+                    "\n    /// The named child outside any field\n    \
+                     pub fn child(&self) -> Option<{ty}<'tree>> {{\n        \
+                         crate::unnamed_field_children(self.0).into_iter().next()\n    }}\n"
+                );
+            }
+        }
+        if !methods.is_empty() {
+            let _ = write!(structs, "\nimpl<'tree> {name}<'tree> {{{methods}}}\n");
+        }
+    }
+
+    let mut code = String::from("// Generated from node-types.json by build.rs; do not edit.\n");
+    code.push_str(&structs);
+    for generated in &enums {
+        code.push_str(&enum_code(
+            &generated.name,
+            "A node of one of several kinds",
+            &generated.kinds,
+        ));
+    }
+    code.push_str(&enum_code(
+        "AnyNode",
+        "Any named node in the grammar",
+        &kinds.iter().cloned().collect(),
+    ));
+    let _ = write!(
+        code,
+        "\n/// Every named node kind in the grammar\npub const KINDS: &[&str] = &[{}];\n",
+        kinds
+            .iter()
+            .map(|kind| format!("{kind:?}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let out = Path::new(&std::env::var("OUT_DIR").expect("OUT_DIR is set")).join("nodes.rs");
+    std::fs::write(out, code).expect("write generated code");
+}
+
+/// The Rust type for the nodes in a field or children entry
+fn child_type(enums: &mut Vec<Enum>, key: &str, info: &Value) -> String {
+    let kinds: BTreeSet<String> = info["types"]
+        .as_array()
+        .expect("types")
+        .iter()
+        .filter(|ty| ty["named"] == true)
+        .filter_map(|ty| ty["type"].as_str())
+        .map(str::to_string)
+        .collect();
+    if kinds.len() == 1 {
+        return type_name(kinds.iter().next().expect("one kind"));
+    }
+    if let Some(existing) = enums.iter().find(|existing| existing.kinds == kinds) {
+        return existing.name.clone();
+    }
+
+    let name = SET_NAMES
+        .iter()
+        .find(|(set_key, _)| *set_key == key)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| {
+            let (kind, field) = key.split_once('.').unwrap_or((key, "child"));
+            format!("{}{}", type_name(kind), pascal_case(field))
+        });
+    enums.push(Enum {
+        name: name.clone(),
+        kinds,
+    });
+    name
+}
+
+fn enum_code(name: &str, doc: &str, kinds: &BTreeSet<String>) -> String {
+    let mut code = format!(
+        "\n/// {doc}\n#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\npub enum {name}<'tree> {{\n"
+    );
+    for kind in kinds {
+        let variant = type_name(kind);
+        let _ = writeln!(code, "    {variant}({variant}<'tree>),");
+    }
+    let _ = write!(
+        code,
+        "}}\n\nimpl<'tree> AstNode<'tree> for {name}<'tree> {{\n    \
+             fn can_cast(kind: &str) -> bool {{\n        matches!(kind, {})\n    }}\n\n    \
+             fn cast(node: Node<'tree>) -> Option<Self> {{\n        match node.kind() {{\n",
+        kinds
+            .iter()
+            .map(|kind| format!("{kind:?}"))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    );
+    for kind in kinds {
+        let variant = type_name(kind);
+        let _ = writeln!(
+            code,
+            "            {kind:?} => Some(Self::{variant}({variant}(node))),"
+        );
+    }
+    code.push_str("            _ => None,\n        }\n    }\n\n    fn node(&self) -> Node<'tree> {\n        match self {\n");
+    for kind in kinds {
+        let _ = writeln!(
+            code,
+            "            Self::{}(node) => node.node(),",
+            type_name(kind)
+        );
+    }
+    code.push_str("        }\n    }\n}\n");
+    code
+}
+
+fn type_name(kind: &str) -> String {
+    TYPE_NAMES
+        .iter()
+        .find(|(name, _)| *name == kind)
+        .map(|(_, ty)| ty.to_string())
+        .unwrap_or_else(|| pascal_case(kind))
+}
+
+fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().expect("non-empty").to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+fn method_name(field: &str) -> String {
+    match field {
+        // These cannot be raw identifiers
+        "self" | "super" | "crate" => format!("{field}_"),
+        "type" | "match" | "ref" | "use" | "impl" | "mod" | "fn" => format!("r#{field}"),
+        _ => field.to_string(),
+    }
+}
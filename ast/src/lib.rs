@@ -0,0 +1,241 @@
+//! Typed syntax tree for Mustache JSON5 templates
+//!
+//! Every named node kind in the grammar has a wrapper struct around a
+//! [`tree_sitter::Node`], generated from `node-types.json` at build time, so
+//! tools match on types and call field accessors instead of comparing kind
+//! strings:
+//!
+//! ```
+//! use mustache_json5_ast::{AstNode, ObjectContent, SourceFile, Value};
+//!
+//! let source = r#"{"name": {{user.name}}}"#;
+//! let tree = mustache_json5_ast::parse(source).unwrap();
+//! let root = SourceFile::cast(tree.root_node()).unwrap();
+//! let object = root.objects().next().unwrap();
+//!
+//! let ObjectContent::Member(member) = object.children()[0] else { panic!() };
+//! assert_eq!(member.name().unwrap().text(source), r#""name""#);
+//! assert!(matches!(member.value(), Some(Value::MustacheInterpolation(_))));
+//! ```
+//!
+//! Sets of kinds that can appear in the same place are enums: [`Value`] for
+//! member values, [`TemplateContent`] for anything in a template body and
+//! [`AnyNode`] for every named node.
+
+use tree_sitter::{Node, Parser, Tree};
+
+include!(concat!(env!("OUT_DIR"), "/nodes.rs"));
+
+/// A typed view of a syntax node
+pub trait AstNode<'tree>: Sized {
+    /// Whether nodes of `kind` can be viewed as this type
+    fn can_cast(kind: &str) -> bool;
+
+    /// View `node` as this type, if it has a matching kind
+    fn cast(node: Node<'tree>) -> Option<Self>;
+
+    /// The underlying syntax node
+    fn node(&self) -> Node<'tree>;
+
+    /// The source text of the node
+    fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.node().byte_range()]
+    }
+}
+
+/// Parse `source` with the Mustache JSON5 grammar
+pub fn parse(source: &str) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_mustache_json5::LANGUAGE.into())
+        .ok()?;
+    parser.parse(source, None)
+}
+
+/// Named children of `node` that are not in a field, viewed as `T`
+fn unnamed_field_children<'tree, T: AstNode<'tree>>(node: Node<'tree>) -> Vec<T> {
+    let mut children = Vec::new();
+    let mut cursor = node.walk();
+    if cursor.goto_first_child() {
+        loop {
+            if cursor.node().is_named() && cursor.field_name().is_none() {
+                children.extend(T::cast(cursor.node()));
+            }
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+    children
+}
+
+impl<'tree> SourceFile<'tree> {
+    /// Every object in the file, outermost first
+    pub fn objects(&self) -> impl Iterator<Item = Object<'tree>> {
+        descendants(self.0).into_iter().filter_map(Object::cast)
+    }
+}
+
+/// `node` and everything below it, in document order
+pub fn descendants(node: Node<'_>) -> Vec<Node<'_>> {
+    let mut nodes = Vec::new();
+    let mut cursor = node.walk();
+    loop {
+        nodes.push(cursor.node());
+        if cursor.goto_first_child() {
+            continue;
+        }
+        loop {
+            if cursor.node() == node {
+                return nodes;
+            }
+            if cursor.goto_next_sibling() {
+                break;
+            }
+            if !cursor.goto_parent() {
+                return nodes;
+            }
+        }
+    }
+}
+
+macro_rules! section_accessors {
+    ($section:ident, $child:ident, $begin:ident, $end:ident) => {
+        impl<'tree> $section<'tree> {
+            /// The opening tag
+            pub fn begin(&self) -> Option<$begin<'tree>> {
+                self.children().into_iter().find_map(|child| match child {
+                    $child::$begin(begin) => Some(begin),
+                    _ => None,
+                })
+            }
+
+            /// The content between the tags
+            pub fn body(&self) -> Vec<TemplateContent<'tree>> {
+                self.children()
+                    .into_iter()
+                    .filter_map(|child| TemplateContent::cast(child.node()))
+                    .collect()
+            }
+
+            /// The closing tag
+            pub fn end(&self) -> Option<$end<'tree>> {
+                self.children().into_iter().find_map(|child| match child {
+                    $child::$end(end) => Some(end),
+                    _ => None,
+                })
+            }
+        }
+
+        impl<'tree> $begin<'tree> {
+            /// The section name, e.g. `items` or the helper name `each`
+            pub fn tag_name(&self) -> Option<TagName<'tree>> {
+                self.children().into_iter().find_map(|child| match child {
+                    TagPart::TagName(name) => Some(name),
+                    _ => None,
+                })
+            }
+
+            /// The helper argument, e.g. `items` in `{{#each items}}`
+            pub fn expression(&self) -> Option<Expression<'tree>> {
+                self.children()
+                    .into_iter()
+                    .find_map(|child| Expression::cast(child.node()))
+            }
+
+            /// Block parameters from `as |item index|`
+            pub fn parameters(&self) -> Vec<Parameter<'tree>> {
+                self.children()
+                    .into_iter()
+                    .find_map(|child| match child {
+                        TagPart::SectionParameters(parameters) => Some(parameters.children()),
+                        _ => None,
+                    })
+                    .unwrap_or_default()
+            }
+        }
+    };
+}
+
+section_accessors!(
+    MustacheSection,
+    SectionChild,
+    MustacheSectionBegin,
+    MustacheSectionEnd
+);
+section_accessors!(
+    MustacheInvertedSection,
+    InvertedSectionChild,
+    MustacheInvertedSectionBegin,
+    MustacheInvertedSectionEnd
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_member_fields() {
+        let source = r#"{"a": 1, b: [true, {{x}}]}"#;
+        let tree = parse(source).unwrap();
+        let root = SourceFile::cast(tree.root_node()).unwrap();
+        let object = root.objects().next().unwrap();
+
+        let members: Vec<Member> = object
+            .children()
+            .into_iter()
+            .filter_map(|child| match child {
+                ObjectContent::Member(member) => Some(member),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[1].name().unwrap().text(source), "b");
+
+        let Some(Value::Array(array)) = members[1].value() else {
+            panic!("expected an array value");
+        };
+        let items = array.children();
+        assert!(matches!(items[0], TemplateContent::True(_)));
+        assert!(matches!(
+            items[1],
+            TemplateContent::MustacheInterpolation(_)
+        ));
+    }
+
+    #[test]
+    fn test_section_accessors() {
+        let source = r#"[{{#each items as |item i|}}{{item}}, {{/each}}]"#;
+        let tree = parse(source).unwrap();
+        let section = descendants(tree.root_node())
+            .into_iter()
+            .find_map(MustacheSection::cast)
+            .unwrap();
+
+        let begin = section.begin().unwrap();
+        assert_eq!(begin.tag_name().unwrap().text(source), "each");
+        assert_eq!(begin.expression().unwrap().text(source), "items");
+        let parameters: Vec<&str> = begin
+            .parameters()
+            .iter()
+            .map(|parameter| parameter.text(source))
+            .collect();
+        assert_eq!(parameters, ["item", "i"]);
+        assert_eq!(section.end().unwrap().text(source), "{{/each}}");
+        assert!(matches!(
+            section.body()[0],
+            TemplateContent::MustacheInterpolation(_)
+        ));
+    }
+
+    #[test]
+    fn test_any_node_covers_every_kind() {
+        assert!(KINDS.iter().all(|kind| AnyNode::can_cast(kind)));
+        assert!(!AnyNode::can_cast("ERROR"));
+
+        let tree = parse("{{! note }}").unwrap();
+        assert!(descendants(tree.root_node())
+            .into_iter()
+            .all(|node| !node.is_named() || AnyNode::cast(node).is_some()));
+    }
+}
@@ -5,6 +5,10 @@
 //!
 //! ```
 //! let code = r#"
+//! {
+//!   "name": {{name}},
+//!   "tags": [{{#tags}}"x"{{/tags}}]
+//! }
 //! "#;
 //! let mut parser = tree_sitter::Parser::new();
 //! let language = tree_sitter_mustache_json5::LANGUAGE;
@@ -34,12 +38,23 @@ pub const LANGUAGE: LanguageFn = unsafe { LanguageFn::from_raw(tree_sitter_musta
 /// [`node-types.json`]: https://tree-sitter.github.io/tree-sitter/using-parsers/6-static-node-types
 pub const NODE_TYPES: &str = include_str!("../../src/node-types.json");
 
-// NOTE: uncomment these to include any queries that this grammar contains:
+/// The syntax highlighting query for this grammar.
+pub const HIGHLIGHTS_QUERY: &str = include_str!("../../queries/highlights.scm");
 
-// pub const HIGHLIGHTS_QUERY: &str = include_str!("../../queries/highlights.scm");
-// pub const INJECTIONS_QUERY: &str = include_str!("../../queries/injections.scm");
-// pub const LOCALS_QUERY: &str = include_str!("../../queries/locals.scm");
-// pub const TAGS_QUERY: &str = include_str!("../../queries/tags.scm");
+/// The language injection query for this grammar.
+pub const INJECTIONS_QUERY: &str = include_str!("../../queries/injections.scm");
+
+/// The local variable query for this grammar.
+pub const LOCALS_QUERY: &str = include_str!("../../queries/locals.scm");
+
+/// The code folding query for this grammar.
+pub const FOLDS_QUERY: &str = include_str!("../../queries/folds.scm");
+
+/// The indentation query for this grammar.
+pub const INDENTS_QUERY: &str = include_str!("../../queries/indents.scm");
+
+/// The lint query for this grammar.
+pub const LINTS_QUERY: &str = include_str!("../../queries/lints.scm");
 
 #[cfg(test)]
 mod tests {
@@ -50,4 +65,21 @@ mod tests {
             .set_language(&super::LANGUAGE.into())
             .expect("Error loading MustacheJson5 parser");
     }
+
+    #[test]
+    fn test_queries_compile() {
+        let language = super::LANGUAGE.into();
+        for (name, source) in [
+            ("highlights", super::HIGHLIGHTS_QUERY),
+            ("injections", super::INJECTIONS_QUERY),
+            ("locals", super::LOCALS_QUERY),
+            ("folds", super::FOLDS_QUERY),
+            ("indents", super::INDENTS_QUERY),
+            ("lints", super::LINTS_QUERY),
+        ] {
+            if let Err(error) = tree_sitter::Query::new(&language, source) {
+                panic!("{}.scm does not compile: {}", name, error);
+            }
+        }
+    }
 }
@@ -0,0 +1,534 @@
+//! Build templates in code instead of concatenating strings
+//!
+//! The builders produce a [`Document`] and only allow what the grammar
+//! accepts in each position: objects hold members, while arrays, sections
+//! and the top level hold values and sections. [`print`] checks the names
+//! and expressions used, formats the result with the given [`Config`] and
+//! parses it again, so the text it returns is a valid template that reads
+//! back as the document that was built.
+//!
+//! ```rust
+//! use mustache_json5_fmt::builder::{arr, each, interp, obj, print, string};
+//! use mustache_json5_fmt::Config;
+//!
+//! let template = obj()
+//!     .member("name", interp("user.name"))
+//!     .member(
+//!         "items",
+//!         arr().section(each("items").param("item").item(string("{{item.id}}"))),
+//!     );
+//! assert_eq!(
+//!     print(template, &Config::default())?,
+//!     "{\n  \"name\": {{user.name}},\n  \"items\": [{{#each items as |item|}}\"{{item.id}}\"{{/each}}]\n}"
+//! );
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use anyhow::{bail, Context, Result};
+
+use crate::config::Config;
+use crate::document::{Document, Node, NodeKind};
+
+/// A value for a member or an array item
+#[derive(Debug, Clone, PartialEq)]
+pub struct Value(Node);
+
+/// A member name: a string for `&str` and `String`, or a mustache tag
+#[derive(Debug, Clone, PartialEq)]
+pub struct Key(Node);
+
+/// An object under construction
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjectBuilder {
+    children: Vec<Node>,
+}
+
+/// An array under construction
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArrayBuilder {
+    children: Vec<Node>,
+}
+
+/// A section or inverted section under construction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    name: String,
+    expression: Option<String>,
+    parameters: Vec<String>,
+    inverted: bool,
+    children: Vec<Node>,
+}
+
+/// A whole template with several top-level items
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+/// An empty object
+pub fn obj() -> ObjectBuilder {
+    ObjectBuilder::default()
+}
+
+/// An empty array
+pub fn arr() -> ArrayBuilder {
+    ArrayBuilder::default()
+}
+
+/// An empty template, for more than one top-level item
+pub fn template() -> Template {
+    Template::default()
+}
+
+/// A string literal; `{{tags}}` in the text are kept as template tags
+pub fn string(text: &str) -> Value {
+    let raw = serde_json::to_string(text).expect("strings serialise to JSON");
+    Value(NodeKind::String { raw }.into())
+}
+
+/// A number literal; infinities and NaN use their JSON5 spelling
+pub fn number(value: impl Into<f64>) -> Value {
+    let value = value.into();
+    let raw = if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else {
+        value.to_string()
+    };
+    Value(NodeKind::Number { raw }.into())
+}
+
+/// `true` or `false`
+pub fn boolean(value: bool) -> Value {
+    Value(NodeKind::Bool { value }.into())
+}
+
+/// `null`
+pub fn null() -> Value {
+    Value(NodeKind::Null.into())
+}
+
+/// `{{expression}}`
+pub fn interp(expression: &str) -> Value {
+    Value(
+        NodeKind::Interpolation {
+            expression: expression.to_string(),
+        }
+        .into(),
+    )
+}
+
+/// `{{{expression}}}`
+pub fn unescaped(expression: &str) -> Value {
+    Value(
+        NodeKind::Unescaped {
+            expression: expression.to_string(),
+        }
+        .into(),
+    )
+}
+
+/// `{{> name}}`
+pub fn partial(name: &str) -> Value {
+    Value(
+        NodeKind::Partial {
+            name: name.to_string(),
+        }
+        .into(),
+    )
+}
+
+/// `{{#name}}...{{/name}}`
+pub fn section(name: &str) -> Section {
+    Section {
+        name: name.to_string(),
+        expression: None,
+        parameters: Vec::new(),
+        inverted: false,
+        children: Vec::new(),
+    }
+}
+
+/// `{{^name}}...{{/name}}`
+pub fn inverted(name: &str) -> Section {
+    Section {
+        inverted: true,
+        ..section(name)
+    }
+}
+
+/// `{{#each expression}}...{{/each}}`
+pub fn each(expression: &str) -> Section {
+    section("each").expression(expression)
+}
+
+impl From<ObjectBuilder> for Value {
+    fn from(object: ObjectBuilder) -> Self {
+        Value(
+            NodeKind::Object {
+                children: object.children,
+            }
+            .into(),
+        )
+    }
+}
+
+impl From<ArrayBuilder> for Value {
+    fn from(array: ArrayBuilder) -> Self {
+        Value(
+            NodeKind::Array {
+                children: array.children,
+            }
+            .into(),
+        )
+    }
+}
+
+impl From<&str> for Key {
+    fn from(name: &str) -> Self {
+        let Value(node) = string(name);
+        Key(node)
+    }
+}
+
+impl From<String> for Key {
+    fn from(name: String) -> Self {
+        Key::from(name.as_str())
+    }
+}
+
+impl From<Value> for Key {
+    /// Use a value such as `interp("key")` as the name; [`print`] rejects
+    /// kinds that cannot be member names
+    fn from(Value(node): Value) -> Self {
+        Key(node)
+    }
+}
+
+impl From<Section> for Node {
+    fn from(section: Section) -> Self {
+        NodeKind::Section {
+            name: section.name,
+            expression: section.expression,
+            parameters: section.parameters,
+            inverted: section.inverted,
+            children: section.children,
+        }
+        .into()
+    }
+}
+
+impl ObjectBuilder {
+    /// Add `key: value`
+    pub fn member(mut self, key: impl Into<Key>, value: impl Into<Value>) -> Self {
+        let Key(name) = key.into();
+        let Value(value) = value.into();
+        self.children.push(
+            NodeKind::Member {
+                name: Box::new(name),
+                value: Box::new(value),
+            }
+            .into(),
+        );
+        self
+    }
+}
+
+impl ArrayBuilder {
+    /// Add an item
+    pub fn item(mut self, value: impl Into<Value>) -> Self {
+        let Value(node) = value.into();
+        self.children.push(node);
+        self
+    }
+
+    /// Add a section, e.g. to repeat items
+    pub fn section(mut self, section: Section) -> Self {
+        self.children.push(section.into());
+        self
+    }
+
+    /// Add `{{! text }}`
+    pub fn mustache_comment(mut self, text: &str) -> Self {
+        self.children.push(mustache_comment(text));
+        self
+    }
+}
+
+impl Section {
+    /// Set the helper argument, e.g. `items` in `{{#each items}}`
+    pub fn expression(mut self, expression: &str) -> Self {
+        self.expression = Some(expression.to_string());
+        self
+    }
+
+    /// Add a block parameter, as in `as |item|`
+    pub fn param(mut self, parameter: &str) -> Self {
+        self.parameters.push(parameter.to_string());
+        self
+    }
+
+    /// Add a value to the body
+    pub fn item(mut self, value: impl Into<Value>) -> Self {
+        let Value(node) = value.into();
+        self.children.push(node);
+        self
+    }
+
+    /// Add a nested section to the body
+    pub fn section(mut self, section: Section) -> Self {
+        self.children.push(section.into());
+        self
+    }
+}
+
+impl Template {
+    /// Add a top-level value
+    pub fn item(mut self, value: impl Into<Value>) -> Self {
+        let Value(node) = value.into();
+        self.nodes.push(node);
+        self
+    }
+
+    /// Add a top-level section
+    pub fn section(mut self, section: Section) -> Self {
+        self.nodes.push(section.into());
+        self
+    }
+
+    /// Add `{{! text }}`
+    pub fn mustache_comment(mut self, text: &str) -> Self {
+        self.nodes.push(mustache_comment(text));
+        self
+    }
+}
+
+impl From<Template> for Document {
+    fn from(template: Template) -> Self {
+        Document {
+            nodes: template.nodes,
+        }
+    }
+}
+
+impl From<Section> for Document {
+    fn from(section: Section) -> Self {
+        Document {
+            nodes: vec![section.into()],
+        }
+    }
+}
+
+impl From<Value> for Document {
+    fn from(Value(node): Value) -> Self {
+        Document { nodes: vec![node] }
+    }
+}
+
+impl From<ObjectBuilder> for Document {
+    fn from(object: ObjectBuilder) -> Self {
+        Value::from(object).into()
+    }
+}
+
+impl From<ArrayBuilder> for Document {
+    fn from(array: ArrayBuilder) -> Self {
+        Value::from(array).into()
+    }
+}
+
+fn mustache_comment(text: &str) -> Node {
+    NodeKind::MustacheComment {
+        text: format!(" {} ", text.trim()),
+    }
+    .into()
+}
+
+/// Format a built template with `config`
+///
+/// Fails if a name, expression or comment could not be written as a tag,
+/// or if the output does not parse back into the same document.
+pub fn print(document: impl Into<Document>, config: &Config) -> Result<String> {
+    let mut document = document.into();
+    for node in &document.nodes {
+        check(node)?;
+    }
+    let formatted = document.format(config)?;
+    let mut parsed = Document::parse(&formatted).context("Built template does not parse")?;
+
+    let mut clear_span = |node: &mut Node| node.span = None;
+    document.walk_mut(&mut clear_span);
+    parsed.walk_mut(&mut clear_span);
+    if parsed != document {
+        bail!(
+            "Built template does not read back as built: `{}`",
+            formatted
+        );
+    }
+    Ok(formatted)
+}
+
+fn check(node: &Node) -> Result<()> {
+    match &node.kind {
+        NodeKind::Interpolation { expression } | NodeKind::Unescaped { expression } => {
+            check_expression(expression)?
+        }
+        NodeKind::Partial { name } => check_name(name, "partial name", &['.', '-'])?,
+        NodeKind::Section {
+            name,
+            expression,
+            parameters,
+            ..
+        } => {
+            check_name(name, "section name", &['.'])?;
+            if let Some(expression) = expression {
+                check_expression(expression)?;
+            }
+            for parameter in parameters {
+                check_name(parameter, "block parameter", &['.'])?;
+            }
+        }
+        NodeKind::MustacheComment { text } if text.contains('}') => {
+            bail!("Mustache comment `{}` cannot contain `}}`", text.trim())
+        }
+        NodeKind::Member { name, .. }
+            if !matches!(
+                name.kind,
+                NodeKind::String { .. }
+                    | NodeKind::Identifier { .. }
+                    | NodeKind::Interpolation { .. }
+                    | NodeKind::Unescaped { .. }
+            ) =>
+        {
+            bail!("Member names must be strings, identifiers or interpolations")
+        }
+        _ => {}
+    }
+    node.children().into_iter().try_for_each(check)
+}
+
+/// `.` or dot-separated identifiers, as in `{{user.name}}`
+fn check_expression(expression: &str) -> Result<()> {
+    let valid = expression == "."
+        || expression.split('.').all(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+    if !valid {
+        bail!("`{}` is not a valid mustache expression", expression);
+    }
+    Ok(())
+}
+
+fn check_name(name: &str, what: &str, extra: &[char]) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || extra.contains(&c));
+    if !valid {
+        bail!("`{}` is not a valid {}", name, what);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_print_object() {
+        let template = obj()
+            .member("id", number(3))
+            .member("label", string("Say \"{{greeting}}\""))
+            .member(interp("key"), boolean(true))
+            .member("extra", null());
+        assert_eq!(
+            print(template, &Config::default()).unwrap(),
+            "{\n  \"id\": 3,\n  \"label\": \"Say \\\"{{greeting}}\\\"\",\n  {{key}}: true,\n  \"extra\": null\n}"
+        );
+    }
+
+    #[test]
+    fn test_print_sections_and_partials() {
+        let template = template().mustache_comment("generated").item(
+            arr()
+                .section(section("admins").item(partial("admin")))
+                .section(inverted("admins").item(string("none"))),
+        );
+        assert_eq!(
+            print(template, &Config::default()).unwrap(),
+            r#"{{! generated }}[{{#admins}}{{> admin}}{{/admins}}, {{^admins}}"none"{{/admins}}]"#
+        );
+    }
+
+    #[test]
+    fn test_print_multi_item_sections() {
+        let template = template()
+            .section(section("xs").item(number(1)).item(number(2)))
+            .item(arr().section(each("rows").item(interp("a")).item(string("b"))));
+        assert_eq!(
+            print(template, &Config::default()).unwrap(),
+            r#"{{#xs}}1 2{{/xs}}[{{#each rows}}{{a}} "b"{{/each}}]"#
+        );
+    }
+
+    #[test]
+    fn test_print_rejects_output_that_reads_back_differently() {
+        // The formatter keeps only members in objects
+        let object = NodeKind::Object {
+            children: vec![
+                NodeKind::Member {
+                    name: Box::new(string("a").0),
+                    value: Box::new(number(1).0),
+                }
+                .into(),
+                section("x").item(string("k")).into(),
+            ],
+        };
+        let error = print(Value(object.into()), &Config::default()).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Built template does not read back"));
+    }
+
+    #[test]
+    fn test_print_uses_config() {
+        let config = Config {
+            indent_size: 4,
+            ..Config::default()
+        };
+        assert_eq!(
+            print(
+                obj().member("a", number(0.5)).member("b", number(-2)),
+                &config
+            )
+            .unwrap(),
+            "{\n    \"a\": 0.5,\n    \"b\": -2\n}"
+        );
+    }
+
+    #[test]
+    fn test_print_rejects_invalid_tags() {
+        let error = print(obj().member("a", interp("a}}{{b")), &Config::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`a}}{{b` is not a valid mustache expression"
+        );
+
+        let error = print(
+            arr().section(each("items").param("x y")),
+            &Config::default(),
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "`x y` is not a valid block parameter");
+
+        let error =
+            print(obj().member(Value::from(arr()), null()), &Config::default()).unwrap_err();
+        assert!(error.to_string().starts_with("Member names"));
+    }
+}
@@ -0,0 +1,197 @@
+//! On-disk cache of files known to be formatted
+//!
+//! Each entry maps a file path to a key derived from the tool version, the
+//! effective configuration and the file content. A file whose current key
+//! matches its entry is already formatted and can be skipped without parsing.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+
+/// Default cache file name, created in the current directory
+pub const DEFAULT_CACHE_FILE: &str = ".mustache-json5-fmt-cache";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    entries: HashMap<String, String>,
+}
+
+pub struct FormatCache {
+    path: PathBuf,
+    config_hash: String,
+    entries: HashMap<String, String>,
+    updates: HashMap<String, String>,
+}
+
+impl FormatCache {
+    /// Load the cache at `path` for the given configuration
+    ///
+    /// A missing or unreadable cache file yields an empty cache rather than an
+    /// error, so a corrupt cache never blocks formatting.
+    pub fn load<P: AsRef<Path>>(path: P, config: &Config) -> Result<Self> {
+        let config_json = serde_json::to_string(config).context("Failed to serialize config")?;
+
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            config_hash: hex_digest(config_json.as_bytes()),
+            entries: read_cache_file(path.as_ref()).entries,
+            updates: HashMap::new(),
+        })
+    }
+
+    /// Check whether `content` of `file` was already recorded as formatted
+    pub fn is_formatted(&self, file: &str, content: &str) -> bool {
+        let key = self.key(content);
+        let file = cache_path(file);
+
+        self.updates
+            .get(&file)
+            .or_else(|| self.entries.get(&file))
+            .is_some_and(|entry| *entry == key)
+    }
+
+    /// Record that `content` is the formatted content of `file`
+    pub fn mark_formatted(&mut self, file: &str, content: &str) {
+        let key = self.key(content);
+        self.updates.insert(cache_path(file), key);
+    }
+
+    /// Write recorded entries back to disk
+    ///
+    /// The cache file is re-read before writing so entries recorded by a
+    /// concurrent run are kept, and the result is written to a temporary file
+    /// and renamed into place so readers never observe a partial write.
+    pub fn save(&self) -> Result<()> {
+        if self.updates.is_empty() {
+            return Ok(());
+        }
+
+        let mut cache = read_cache_file(&self.path);
+        cache.entries.extend(self.updates.clone());
+
+        let content = serde_json::to_string(&cache).context("Failed to serialize cache")?;
+
+        let file_name = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| DEFAULT_CACHE_FILE.to_string());
+        let temp_path =
+            self.path
+                .with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+        fs::write(&temp_path, content)
+            .with_context(|| format!("Failed to write cache file: {}", temp_path.display()))?;
+        if let Err(e) = fs::rename(&temp_path, &self.path) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e)
+                .with_context(|| format!("Failed to write cache file: {}", self.path.display()));
+        }
+
+        Ok(())
+    }
+
+    fn key(&self, content: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.update([0]);
+        hasher.update(self.config_hash.as_bytes());
+        hasher.update([0]);
+        hasher.update(content.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+fn read_cache_file(path: &Path) -> CacheFile {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn cache_path(file: &str) -> String {
+    fs::canonicalize(file)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| file.to_string())
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cache_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let cache_file = dir.path().join(DEFAULT_CACHE_FILE);
+        let template = dir.path().join("test.mjson5");
+        let template = template.to_str().unwrap();
+        let config = Config::default();
+
+        let mut cache = FormatCache::load(&cache_file, &config).unwrap();
+        assert!(!cache.is_formatted(template, "{}"));
+
+        cache.mark_formatted(template, "{}");
+        cache.save().unwrap();
+
+        let cache = FormatCache::load(&cache_file, &config).unwrap();
+        assert!(cache.is_formatted(template, "{}"));
+        assert!(!cache.is_formatted(template, "[]"));
+    }
+
+    #[test]
+    fn test_cache_invalidated_by_config() {
+        let dir = TempDir::new().unwrap();
+        let cache_file = dir.path().join(DEFAULT_CACHE_FILE);
+        let template = "test.mjson5";
+
+        let mut cache = FormatCache::load(&cache_file, &Config::default()).unwrap();
+        cache.mark_formatted(template, "{}");
+        cache.save().unwrap();
+
+        let config = Config {
+            use_tabs: true,
+            ..Config::default()
+        };
+        let cache = FormatCache::load(&cache_file, &config).unwrap();
+        assert!(!cache.is_formatted(template, "{}"));
+    }
+
+    #[test]
+    fn test_save_keeps_concurrent_entries() {
+        let dir = TempDir::new().unwrap();
+        let cache_file = dir.path().join(DEFAULT_CACHE_FILE);
+        let config = Config::default();
+
+        let mut first = FormatCache::load(&cache_file, &config).unwrap();
+        let mut second = FormatCache::load(&cache_file, &config).unwrap();
+
+        first.mark_formatted("a.mjson5", "{}");
+        second.mark_formatted("b.mjson5", "[]");
+        first.save().unwrap();
+        second.save().unwrap();
+
+        let cache = FormatCache::load(&cache_file, &config).unwrap();
+        assert!(cache.is_formatted("a.mjson5", "{}"));
+        assert!(cache.is_formatted("b.mjson5", "[]"));
+    }
+
+    #[test]
+    fn test_corrupt_cache_is_ignored() {
+        let dir = TempDir::new().unwrap();
+        let cache_file = dir.path().join(DEFAULT_CACHE_FILE);
+        fs::write(&cache_file, "not json").unwrap();
+
+        let cache = FormatCache::load(&cache_file, &Config::default()).unwrap();
+        assert!(!cache.is_formatted("a.mjson5", "{}"));
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 
@@ -34,29 +35,35 @@ pub struct Config {
 
     /// How to handle comments
     pub comment_handling: CommentHandling,
+
+    /// Lint rule settings, for `mustache-json5-fmt lint`
+    #[serde(default, skip_serializing_if = "LintConfig::is_empty")]
+    pub lint: LintConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum TrailingCommaStyle {
     /// Never add trailing commas
     Never,
     /// Add trailing commas where valid in JSON5
     Always,
     /// Keep existing trailing commas, don't add new ones
+    #[default]
     Preserve,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum QuoteStyle {
     /// Always use double quotes
     Double,
     /// Always use single quotes (where possible in JSON5)
     Single,
     /// Preserve existing quote style
+    #[default]
     Preserve,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MustacheSpacing {
     /// Space after opening delimiter: {{ vs {{
     pub after_open: bool,
@@ -66,9 +73,10 @@ pub struct MustacheSpacing {
     pub around_operators: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum MustacheIndentStyle {
     /// Indent mustache sections like code blocks
+    #[default]
     Block,
     /// Preserve original indentation
     Preserve,
@@ -84,6 +92,69 @@ pub struct CommentHandling {
     pub normalize_spacing: bool,
 }
 
+/// The `lint` section of the configuration file
+///
+/// ```json
+/// "lint": {
+///   "rules": {
+///     "unsafe-unescaped": "off",
+///     "duplicate-key": "error",
+///     "deep-nesting": { "severity": "warn", "max_depth": 5 }
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LintConfig {
+    /// Settings by rule ID; rules not listed keep their defaults
+    #[serde(default)]
+    pub rules: BTreeMap<String, RuleConfig>,
+}
+
+impl LintConfig {
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+/// A rule's level, optionally with rule-specific options
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RuleConfig {
+    Level(RuleLevel),
+    Options {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        severity: Option<RuleLevel>,
+        #[serde(flatten)]
+        options: serde_json::Map<String, serde_json::Value>,
+    },
+}
+
+impl RuleConfig {
+    /// The configured level, if any
+    pub fn level(&self) -> Option<RuleLevel> {
+        match self {
+            RuleConfig::Level(level) => Some(*level),
+            RuleConfig::Options { severity, .. } => *severity,
+        }
+    }
+
+    /// Rule-specific options such as `max_depth`
+    pub fn options(&self) -> serde_json::Map<String, serde_json::Value> {
+        match self {
+            RuleConfig::Level(_) => serde_json::Map::new(),
+            RuleConfig::Options { options, .. } => options.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleLevel {
+    Off,
+    Warn,
+    Error,
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -104,6 +175,7 @@ impl Default for Config {
                 preserve_formatting: true,
                 normalize_spacing: false,
             },
+            lint: LintConfig::default(),
         }
     }
 }
@@ -196,34 +268,6 @@ impl Config {
     }
 }
 
-impl Default for TrailingCommaStyle {
-    fn default() -> Self {
-        TrailingCommaStyle::Preserve
-    }
-}
-
-impl Default for QuoteStyle {
-    fn default() -> Self {
-        QuoteStyle::Preserve
-    }
-}
-
-impl Default for MustacheSpacing {
-    fn default() -> Self {
-        Self {
-            after_open: false,
-            before_close: false,
-            around_operators: false,
-        }
-    }
-}
-
-impl Default for MustacheIndentStyle {
-    fn default() -> Self {
-        MustacheIndentStyle::Block
-    }
-}
-
 impl Default for CommentHandling {
     fn default() -> Self {
         Self {
@@ -249,8 +293,10 @@ mod tests {
 
     #[test]
     fn test_tab_config() {
-        let mut config = Config::default();
-        config.use_tabs = true;
+        let config = Config {
+            use_tabs: true,
+            ..Config::default()
+        };
         assert_eq!(config.indent_string(), "\t");
     }
 
@@ -277,6 +323,27 @@ mod tests {
         assert_eq!(config.use_tabs, loaded_config.use_tabs);
     }
 
+    #[test]
+    fn test_lint_config() {
+        let mut json = serde_json::to_value(Config::default()).unwrap();
+        assert!(json.get("lint").is_none());
+
+        json["lint"] = serde_json::json!({
+            "rules": {
+                "unsafe-unescaped": "off",
+                "deep-nesting": {"severity": "error", "max_depth": 5},
+                "unused-param": {"max_depth": 1}
+            }
+        });
+        let config: Config = serde_json::from_value(json).unwrap();
+        let rules = &config.lint.rules;
+
+        assert_eq!(rules["unsafe-unescaped"].level(), Some(RuleLevel::Off));
+        assert_eq!(rules["deep-nesting"].level(), Some(RuleLevel::Error));
+        assert_eq!(rules["deep-nesting"].options()["max_depth"], 5);
+        assert_eq!(rules["unused-param"].level(), None);
+    }
+
     #[test]
     fn test_config_validation() {
         let mut config = Config::default();
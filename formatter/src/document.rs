@@ -0,0 +1,534 @@
+//! Owned document model for codemods and tooling
+//!
+//! A [`Document`] holds JSON5 values interleaved with mustache tags and
+//! comments, detached from the source text. It can be read from a template,
+//! built or edited in code, serialised with serde, and printed back through
+//! [`MustacheJson5Formatter`](crate::MustacheJson5Formatter):
+//!
+//! ```rust
+//! use mustache_json5_fmt::document::{Document, NodeKind};
+//! use mustache_json5_fmt::Config;
+//!
+//! let mut document = Document::parse(r#"{"name": {{user.name}}}"#)?;
+//! document.walk_mut(&mut |node| {
+//!     if let NodeKind::Interpolation { expression } = &mut node.kind {
+//!         *expression = expression.replace("user.", "account.");
+//!     }
+//! });
+//! assert_eq!(document.to_source(), r#"{"name": {{account.name}}}"#);
+//! println!("{}", document.format(&Config::default())?);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use anyhow::{bail, Context, Result};
+use mustache_json5_ast::{AnyNode, AstNode, TagPart};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use tree_sitter::Node as SyntaxNode;
+
+use crate::config::Config;
+use crate::formatter::MustacheJson5Formatter;
+
+/// A whole template
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    pub nodes: Vec<Node>,
+}
+
+/// A value, member, tag or comment, with where it came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    #[serde(flatten)]
+    pub kind: NodeKind,
+    /// Byte range in the parsed source; `None` for nodes built in code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Range<usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NodeKind {
+    Object {
+        children: Vec<Node>,
+    },
+    Array {
+        children: Vec<Node>,
+    },
+    /// `name: value`; the name is a string, identifier or mustache tag
+    Member {
+        name: Box<Node>,
+        value: Box<Node>,
+    },
+    /// A string literal as written, quotes and embedded tags included
+    String {
+        raw: String,
+    },
+    Number {
+        raw: String,
+    },
+    /// An unquoted member name
+    Identifier {
+        name: String,
+    },
+    Bool {
+        value: bool,
+    },
+    Null,
+    /// `{{expression}}`
+    Interpolation {
+        expression: String,
+    },
+    /// `{{{expression}}}` or `{{&expression}}`
+    Unescaped {
+        expression: String,
+    },
+    /// `{{> name}}`
+    Partial {
+        name: String,
+    },
+    /// `{{#name}}...{{/name}}` or, when inverted, `{{^name}}...{{/name}}`;
+    /// `expression` and `parameters` are set for block helpers such as
+    /// `{{#each items as |item|}}`
+    Section {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expression: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        parameters: Vec<String>,
+        #[serde(default)]
+        inverted: bool,
+        children: Vec<Node>,
+    },
+    /// `{{! text}}`
+    MustacheComment {
+        text: String,
+    },
+    /// A JSON5 `//` or `/* */` comment, as written
+    Comment {
+        text: String,
+    },
+    /// Template text outside the JSON5 structure
+    Text {
+        text: String,
+    },
+}
+
+impl From<NodeKind> for Node {
+    fn from(kind: NodeKind) -> Self {
+        Node { kind, span: None }
+    }
+}
+
+impl Node {
+    /// Child nodes in document order
+    pub fn children(&self) -> Vec<&Node> {
+        match &self.kind {
+            NodeKind::Object { children }
+            | NodeKind::Array { children }
+            | NodeKind::Section { children, .. } => children.iter().collect(),
+            NodeKind::Member { name, value } => vec![name.as_ref(), value.as_ref()],
+            _ => Vec::new(),
+        }
+    }
+
+    fn walk_mut(&mut self, f: &mut impl FnMut(&mut Node)) {
+        f(self);
+        match &mut self.kind {
+            NodeKind::Object { children }
+            | NodeKind::Array { children }
+            | NodeKind::Section { children, .. } => {
+                for child in children {
+                    child.walk_mut(f);
+                }
+            }
+            NodeKind::Member { name, value } => {
+                name.walk_mut(f);
+                value.walk_mut(f);
+            }
+            _ => {}
+        }
+    }
+
+    fn write(&self, out: &mut String) {
+        match &self.kind {
+            NodeKind::Object { children } => write_list(children, '{', '}', out),
+            NodeKind::Array { children } => write_list(children, '[', ']', out),
+            NodeKind::Member { name, value } => {
+                name.write(out);
+                out.push_str(": ");
+                value.write(out);
+            }
+            NodeKind::String { raw } | NodeKind::Number { raw } => out.push_str(raw),
+            NodeKind::Identifier { name } => out.push_str(name),
+            NodeKind::Bool { value } => out.push_str(if *value { "true" } else { "false" }),
+            NodeKind::Null => out.push_str("null"),
+            NodeKind::Interpolation { expression } => {
+                out.push_str(&format!("{{{{{}}}}}", expression))
+            }
+            NodeKind::Unescaped { expression } => {
+                out.push_str(&format!("{{{{{{{}}}}}}}", expression))
+            }
+            NodeKind::Partial { name } => out.push_str(&format!("{{{{> {}}}}}", name)),
+            NodeKind::Section {
+                name,
+                expression,
+                parameters,
+                inverted,
+                children,
+            } => {
+                out.push_str(if *inverted { "{{^" } else { "{{#" });
+                out.push_str(name);
+                if let Some(expression) = expression {
+                    out.push(' ');
+                    out.push_str(expression);
+                }
+                if !parameters.is_empty() {
+                    out.push_str(&format!(" as |{}|", parameters.join(" ")));
+                }
+                out.push_str("}}");
+                write_sequence(children, out);
+                out.push_str(&format!("{{{{/{}}}}}", name));
+            }
+            NodeKind::MustacheComment { text } => out.push_str(&format!("{{{{!{}}}}}", text)),
+            NodeKind::Comment { text } => {
+                out.push_str(text);
+                // Line comments run to the end of the line
+                if text.starts_with("//") {
+                    out.push('\n');
+                }
+            }
+            NodeKind::Text { text } => out.push_str(text),
+        }
+    }
+}
+
+/// Write `nodes` one after another, separated by a space so that adjacent
+/// values stay apart; text keeps its own whitespace
+fn write_sequence(nodes: &[Node], out: &mut String) {
+    let is_text = |node: &Node| matches!(node.kind, NodeKind::Text { .. });
+    for (i, node) in nodes.iter().enumerate() {
+        if i > 0 && !is_text(&nodes[i - 1]) && !is_text(node) && !out.ends_with('\n') {
+            out.push(' ');
+        }
+        node.write(out);
+    }
+}
+
+/// Write `children` between brackets, separated by commas; JSON5 comments
+/// are not elements, so they take no comma
+fn write_list(children: &[Node], open: char, close: char, out: &mut String) {
+    out.push(open);
+    let elements = children
+        .iter()
+        .filter(|child| !matches!(child.kind, NodeKind::Comment { .. }))
+        .count();
+    let mut written = 0;
+    for child in children {
+        if !matches!(child.kind, NodeKind::Comment { .. }) {
+            written += 1;
+        }
+        child.write(out);
+        if !matches!(child.kind, NodeKind::Comment { .. }) && written < elements {
+            out.push_str(", ");
+        }
+    }
+    out.push(close);
+}
+
+impl Document {
+    /// Read a template; fails if it does not parse cleanly
+    pub fn parse(source: &str) -> Result<Self> {
+        let tree = mustache_json5_ast::parse(source).context("Failed to parse source code")?;
+        let root = tree.root_node();
+        if root.has_error() {
+            let error = first_error(root).unwrap_or(root);
+            let position = error.start_position();
+            bail!(
+                "Syntax error at line {}, column {}",
+                position.row + 1,
+                position.column + 1
+            );
+        }
+
+        let mut nodes = Vec::new();
+        convert_children(root, source, &mut nodes);
+        Ok(Document { nodes })
+    }
+
+    /// Visit every node, parents before children
+    pub fn walk_mut(&mut self, f: &mut impl FnMut(&mut Node)) {
+        for node in &mut self.nodes {
+            node.walk_mut(f);
+        }
+    }
+
+    /// Template source for the document, on one line
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        write_sequence(&self.nodes, &mut out);
+        out
+    }
+
+    /// Print the document with the formatter's layout rules
+    pub fn format(&self, config: &Config) -> Result<String> {
+        MustacheJson5Formatter::new(config.clone())?.format(&self.to_source())
+    }
+}
+
+fn first_error(node: SyntaxNode) -> Option<SyntaxNode> {
+    if node.is_error() || node.is_missing() {
+        return Some(node);
+    }
+    let mut cursor = node.walk();
+    let children: Vec<_> = node.children(&mut cursor).collect();
+    children.into_iter().find_map(first_error)
+}
+
+/// Convert the named children of `node`, skipping wrappers such as
+/// `document` that have no counterpart in the model
+fn convert_children(node: SyntaxNode, source: &str, out: &mut Vec<Node>) {
+    let mut cursor = node.walk();
+    let children: Vec<_> = node.named_children(&mut cursor).collect();
+    for child in children {
+        match AnyNode::cast(child) {
+            Some(
+                AnyNode::SourceFile(_)
+                | AnyNode::Document(_)
+                | AnyNode::Json5Document(_)
+                | AnyNode::TemplateDocument(_),
+            ) => convert_children(child, source, out),
+            Some(
+                AnyNode::MustacheSectionBegin(_)
+                | AnyNode::MustacheSectionEnd(_)
+                | AnyNode::MustacheInvertedSectionBegin(_)
+                | AnyNode::MustacheInvertedSectionEnd(_),
+            ) => {}
+            Some(any) => out.extend(convert(any, source)),
+            None => {}
+        }
+    }
+}
+
+fn convert(any: AnyNode, source: &str) -> Option<Node> {
+    let syntax = any.node();
+    let raw = || source[syntax.byte_range()].to_string();
+    let children = || {
+        let mut nodes = Vec::new();
+        convert_children(syntax, source, &mut nodes);
+        nodes
+    };
+    // Text of the tag's first named child, e.g. the expression or name
+    let inner = || {
+        syntax
+            .named_child(0)
+            .map(|child| source[child.byte_range()].trim().to_string())
+            .unwrap_or_default()
+    };
+
+    let kind = match any {
+        AnyNode::Object(_) => NodeKind::Object {
+            children: children(),
+        },
+        AnyNode::Array(_) => NodeKind::Array {
+            children: children(),
+        },
+        AnyNode::Member(member) => {
+            let name = member.name()?.node().named_child(0)?;
+            let value = member.value()?.node();
+            NodeKind::Member {
+                name: Box::new(convert(AnyNode::cast(name)?, source)?),
+                value: Box::new(convert(AnyNode::cast(value)?, source)?),
+            }
+        }
+        AnyNode::StringLiteral(_) => NodeKind::String { raw: raw() },
+        AnyNode::Number(_) => NodeKind::Number { raw: raw() },
+        AnyNode::Identifier(_) => NodeKind::Identifier { name: raw() },
+        AnyNode::True(_) => NodeKind::Bool { value: true },
+        AnyNode::False(_) => NodeKind::Bool { value: false },
+        AnyNode::Null(_) => NodeKind::Null,
+        AnyNode::MustacheInterpolation(_) => NodeKind::Interpolation {
+            expression: inner(),
+        },
+        AnyNode::MustacheUnescaped(_) => NodeKind::Unescaped {
+            expression: inner(),
+        },
+        AnyNode::MustachePartial(_) => NodeKind::Partial { name: inner() },
+        AnyNode::MustacheSection(section) => {
+            let begin = section.begin()?;
+            section_kind(
+                begin.children(),
+                false,
+                section.body().iter().map(|child| child.node()),
+                source,
+            )
+        }
+        AnyNode::MustacheInvertedSection(section) => {
+            let begin = section.begin()?;
+            section_kind(
+                begin.children(),
+                true,
+                section.body().iter().map(|child| child.node()),
+                source,
+            )
+        }
+        AnyNode::MustacheComment(_) => {
+            let tag = raw();
+            NodeKind::MustacheComment {
+                text: tag
+                    .strip_prefix("{{!")
+                    .and_then(|rest| rest.strip_suffix("}}"))
+                    .unwrap_or(&tag)
+                    .to_string(),
+            }
+        }
+        AnyNode::Comment(_) => NodeKind::Comment { text: raw() },
+        AnyNode::Text(_) => NodeKind::Text { text: raw() },
+        _ => return None,
+    };
+    Some(Node {
+        kind,
+        span: Some(syntax.byte_range()),
+    })
+}
+
+fn section_kind<'tree>(
+    tag: Vec<TagPart<'tree>>,
+    inverted: bool,
+    body: impl Iterator<Item = SyntaxNode<'tree>>,
+    source: &str,
+) -> NodeKind {
+    let text = |node: SyntaxNode| source[node.byte_range()].trim().to_string();
+    let mut name = String::new();
+    let mut expression = None;
+    let mut parameters = Vec::new();
+    for part in tag {
+        match part {
+            TagPart::TagName(tag_name) => name = text(tag_name.node()),
+            TagPart::SectionParameters(list) => {
+                parameters = list
+                    .children()
+                    .iter()
+                    .map(|parameter| text(parameter.node()))
+                    .collect();
+            }
+            other => expression = Some(text(other.node())),
+        }
+    }
+    NodeKind::Section {
+        name,
+        expression,
+        parameters,
+        inverted,
+        children: body
+            .filter_map(|node| convert(AnyNode::cast(node)?, source))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_and_print_round_trip() {
+        let source = r#"{"name": "{{user.name}}", tags: [{{#each tags as |tag|}}{{tag}}{{/each}}, {{{raw}}}], "n": null // note
+}"#;
+        let document = Document::parse(source).unwrap();
+        assert_eq!(
+            document.to_source(),
+            r#"{"name": "{{user.name}}", tags: [{{#each tags as |tag|}}{{tag}}{{/each}}, {{{raw}}}], "n": null// note
+}"#
+        );
+        assert_eq!(
+            Document::parse(&document.to_source()).unwrap().to_source(),
+            document.to_source()
+        );
+    }
+
+    #[test]
+    fn test_print_keeps_sequences_apart() {
+        for source in [
+            "[{{#xs}}1 2{{/xs}}]",
+            "{{#xs}}{{a}} \"b\" {{{c}}}{{/xs}}",
+            "{{a}} {{b}}",
+        ] {
+            let document = Document::parse(source).unwrap();
+            assert_eq!(document.to_source(), source);
+            assert_eq!(Document::parse(&document.to_source()).unwrap(), document);
+        }
+    }
+
+    #[test]
+    fn test_serialize_to_json() {
+        let document = Document::parse(r#"[1, {{^empty}}true{{/empty}}]"#).unwrap();
+        assert_eq!(
+            serde_json::to_value(&document).unwrap(),
+            json!({"nodes": [{
+                "type": "array",
+                "span": {"start": 0, "end": 29},
+                "children": [
+                    {"type": "number", "raw": "1", "span": {"start": 1, "end": 2}},
+                    {
+                        "type": "section",
+                        "name": "empty",
+                        "inverted": true,
+                        "span": {"start": 4, "end": 28},
+                        "children": [{"type": "bool", "value": true, "span": {"start": 14, "end": 18}}]
+                    }
+                ]
+            }]})
+        );
+
+        let parsed: Document =
+            serde_json::from_value(serde_json::to_value(&document).unwrap()).unwrap();
+        assert_eq!(parsed, document);
+    }
+
+    #[test]
+    fn test_build_mutate_and_format() {
+        let mut document = Document {
+            nodes: vec![NodeKind::Object {
+                children: vec![NodeKind::Member {
+                    name: Box::new(
+                        NodeKind::String {
+                            raw: "\"id\"".into(),
+                        }
+                        .into(),
+                    ),
+                    value: Box::new(
+                        NodeKind::Interpolation {
+                            expression: "id".into(),
+                        }
+                        .into(),
+                    ),
+                }
+                .into()],
+            }
+            .into()],
+        };
+        document.walk_mut(&mut |node| {
+            if let NodeKind::Object { children } = &mut node.kind {
+                children.push(
+                    NodeKind::Member {
+                        name: Box::new(NodeKind::Identifier { name: "ok".into() }.into()),
+                        value: Box::new(NodeKind::Bool { value: true }.into()),
+                    }
+                    .into(),
+                );
+            }
+        });
+
+        assert_eq!(document.to_source(), r#"{"id": {{id}}, ok: true}"#);
+        assert_eq!(
+            document.format(&Config::default()).unwrap(),
+            "{\n  \"id\": {{id}},\n  ok: true\n}"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_syntax_errors() {
+        let error = Document::parse(r#"{"a": 1 "b": 2}"#).unwrap_err();
+        assert!(error.to_string().starts_with("Syntax error at line 1"));
+    }
+}
@@ -1,7 +1,9 @@
 use anyhow::{Context, Result};
+use mustache_json5_ast::{AnyNode, AstNode};
 use std::collections::HashMap;
+use std::ops::Range;
 use tree_sitter::{Node, Parser, Query, Tree};
-use tree_sitter_mustache_json5::LANGUAGE;
+use tree_sitter_mustache_json5::{INDENTS_QUERY, LANGUAGE};
 
 use crate::config::Config;
 
@@ -21,36 +23,8 @@ impl MustacheJson5Formatter {
             .set_language(&language)
             .context("Failed to set language for parser")?;
 
-        // Load indentation queries - embedded basic rules for now
-        let indent_query_source = r#"
-; Increase indent for JSON5 structures
-(object "{" @indent.begin)
-(object "}" @indent.end)
-
-(array "[" @indent.begin)
-(array "]" @indent.end)
-
-; Increase indent for Mustache sections
-(mustache_section
-  (mustache_section_begin) @indent.begin)
-(mustache_section
-  (mustache_section_end) @indent.end)
-
-(mustache_inverted_section
-  (mustache_inverted_section_begin) @indent.begin)
-(mustache_inverted_section
-  (mustache_inverted_section_end) @indent.end)
-
-; Align object members
-(object
-  (member) @indent.align)
-
-; Align array elements
-(array
-  (_) @indent.align)
-"#;
-        let indent_query = Query::new(&language, indent_query_source)
-            .context("Failed to parse indentation query")?;
+        let indent_query =
+            Query::new(&language, INDENTS_QUERY).context("Failed to parse indentation query")?;
 
         Ok(Self {
             parser,
@@ -78,6 +52,73 @@ impl MustacheJson5Formatter {
         Ok(formatted)
     }
 
+    /// Format only the parts of `source` that touch the given line ranges
+    ///
+    /// `lines` are zero-based, half-open row ranges. Each range is widened to
+    /// the smallest member, array element, object or array that covers it
+    /// and starts its own line; those nodes are formatted at their nesting
+    /// level and spliced back, so the rest of the file keeps its layout.
+    pub fn format_lines(&mut self, source: &str, lines: &[Range<usize>]) -> Result<String> {
+        let tree = self
+            .parser
+            .parse(source, None)
+            .context("Failed to parse source code")?;
+
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        let mut nodes: Vec<Node> = Vec::new();
+        for range in lines {
+            let (Some(&start), true) = (line_starts.get(range.start), range.end > range.start)
+            else {
+                continue;
+            };
+            let end = line_starts.get(range.end).copied().unwrap_or(source.len());
+            // Only the text on the lines counts, not the indentation or the
+            // separating comma around it
+            let text = &source[start..end];
+            let Some(first) = text.find(|c: char| !c.is_whitespace()) else {
+                continue;
+            };
+            let last = text.trim_end().trim_end_matches(',').len().max(first + 1);
+            let Some(node) = tree
+                .root_node()
+                .descendant_for_byte_range(start + first, start + last)
+            else {
+                continue;
+            };
+            match formattable_ancestor(node, source) {
+                Some(node) => nodes.push(node),
+                None => return self.format(source),
+            }
+        }
+
+        // Outermost nodes first, dropping any nested in one already chosen
+        nodes.sort_by_key(|node| (node.start_byte(), std::cmp::Reverse(node.end_byte())));
+        nodes.dedup_by(|inner, outer| outer.end_byte() >= inner.end_byte());
+
+        let mut formatter = NodeFormatter::new(&self.config, source, &self.indent_query);
+        let mut result = String::with_capacity(source.len());
+        let mut copied = 0;
+        for node in nodes {
+            let depth = std::iter::successors(node.parent(), |node| node.parent())
+                .filter(|ancestor| matches!(ancestor.kind(), "object" | "array"))
+                .count();
+            let line_start = line_starts[node.start_position().row];
+            result.push_str(&source[copied..line_start]);
+            result.push_str(&formatter.indent_string(depth));
+            let formatted = match node.kind() {
+                "member" => formatter.format_object_member(node, depth)?,
+                _ => formatter.format_node(node, depth)?,
+            };
+            result.push_str(&formatted);
+            copied = node.end_byte();
+        }
+        result.push_str(&source[copied..]);
+
+        Ok(result)
+    }
+
     fn format_tree(&self, tree: &Tree, source: &str) -> Result<String> {
         let root_node = tree.root_node();
         let mut formatter = NodeFormatter::new(&self.config, source, &self.indent_query);
@@ -85,6 +126,25 @@ impl MustacheJson5Formatter {
     }
 }
 
+/// The nearest node at or above `node` that can be formatted on its own: a
+/// member, an array element, an object or an array starting its own line.
+/// `None` when only the whole document will do.
+fn formattable_ancestor<'tree>(node: Node<'tree>, source: &str) -> Option<Node<'tree>> {
+    std::iter::successors(Some(node), |node| node.parent()).find_map(|node| {
+        let unit = matches!(node.kind(), "member" | "object" | "array")
+            || node.parent().is_some_and(|parent| parent.kind() == "array");
+        let line_start = source[..node.start_byte()]
+            .rfind('\n')
+            .map_or(0, |index| index + 1);
+        let starts_line = source[line_start..node.start_byte()].trim().is_empty();
+        match node.kind() {
+            "source_file" | "document" | "json5_document" | "template_document" => Some(None),
+            _ if unit && starts_line && !node.has_error() => Some(Some(node)),
+            _ => None,
+        }
+    })?
+}
+
 struct NodeFormatter<'a> {
     config: &'a Config,
     source: &'a str,
@@ -110,8 +170,13 @@ impl<'a> NodeFormatter<'a> {
         // Get indentation hints from tree-sitter queries
         let _indent_changes = self.get_indent_changes(node);
 
-        match node.kind() {
-            "source_file" | "document" | "json5_document" | "template_document" => {
+        match AnyNode::cast(node) {
+            Some(
+                AnyNode::SourceFile(_)
+                | AnyNode::Document(_)
+                | AnyNode::Json5Document(_)
+                | AnyNode::TemplateDocument(_),
+            ) => {
                 for child in node.children(&mut node.walk()) {
                     if !child.is_named() {
                         continue;
@@ -119,44 +184,52 @@ impl<'a> NodeFormatter<'a> {
                     result.push_str(&self.format_node(child, current_indent)?);
                 }
             }
-            "object" => {
+            Some(AnyNode::Object(_)) => {
                 result.push_str(&self.format_object(node, current_indent)?);
             }
-            "array" => {
+            Some(AnyNode::Array(_)) => {
                 result.push_str(&self.format_array(node, current_indent)?);
             }
-            "mustache_section" => {
+            Some(AnyNode::MustacheSection(_)) => {
                 result.push_str(&self.format_mustache_section(node, current_indent)?);
             }
-            "mustache_inverted_section" => {
+            Some(AnyNode::MustacheInvertedSection(_)) => {
                 result.push_str(&self.format_mustache_inverted_section(node, current_indent)?);
             }
-            "mustache_interpolation" => {
+            Some(AnyNode::MustacheInterpolation(_)) => {
                 result.push_str(&self.format_mustache_interpolation(node)?);
             }
-            "mustache_unescaped" => {
+            Some(AnyNode::MustacheUnescaped(_)) => {
                 result.push_str(&self.format_mustache_unescaped(node)?);
             }
-            "mustache_partial" => {
+            Some(AnyNode::MustachePartial(_)) => {
                 result.push_str(&self.format_mustache_partial(node)?);
             }
-            "mustache_comment" => {
+            Some(AnyNode::MustacheComment(_)) => {
                 result.push_str(&self.format_mustache_comment(node, current_indent)?);
             }
-            "comment" => {
+            Some(AnyNode::Comment(_)) => {
                 result.push_str(&self.format_json5_comment(node, current_indent)?);
             }
-            "string" | "number" | "true" | "false" | "null" | "identifier" | "name" => {
+            Some(
+                AnyNode::StringLiteral(_)
+                | AnyNode::Number(_)
+                | AnyNode::True(_)
+                | AnyNode::False(_)
+                | AnyNode::Null(_)
+                | AnyNode::Identifier(_)
+                | AnyNode::Name(_),
+            ) => {
                 // Handle primitive values and names directly
-                result.push_str(&self.get_node_text(node));
+                result.push_str(self.get_node_text(node));
             }
-            "text" => {
+            Some(AnyNode::Text(_)) => {
                 // Handle text nodes (may contain mustache content)
-                result.push_str(&self.get_node_text(node));
+                result.push_str(self.get_node_text(node));
             }
-            "ERROR" => {
+            None if node.is_error() => {
                 // Handle error nodes by preserving their content
-                result.push_str(&self.get_node_text(node));
+                result.push_str(self.get_node_text(node));
             }
             _ => {
                 // For other nodes, check if they have children to format
@@ -165,12 +238,12 @@ impl<'a> NodeFormatter<'a> {
                         if child.is_named() {
                             result.push_str(&self.format_node(child, current_indent)?);
                         } else {
-                            result.push_str(&self.get_node_text(child));
+                            result.push_str(self.get_node_text(child));
                         }
                     }
                 } else {
                     // Leaf node, preserve original content
-                    result.push_str(&self.get_node_text(node));
+                    result.push_str(self.get_node_text(node));
                 }
             }
         }
@@ -328,7 +401,7 @@ impl<'a> NodeFormatter<'a> {
         let mut cursor = node.walk();
         let children: Vec<Node> = node.children(&mut cursor).collect();
 
-        for (_i, child) in children.iter().enumerate() {
+        for child in children.iter() {
             match child.kind() {
                 ":" => {
                     result.push_str(": ");
@@ -364,7 +437,7 @@ impl<'a> NodeFormatter<'a> {
     fn format_mustache_comment(&mut self, node: Node, indent: usize) -> Result<String> {
         let mut result = String::new();
         result.push_str(&self.indent_string(indent));
-        result.push_str(&self.get_node_text(node));
+        result.push_str(self.get_node_text(node));
         Ok(result)
     }
 
@@ -447,6 +520,46 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_format_lines_only_touches_requested_lines() {
+        let config = Config::default();
+        let mut formatter = MustacheJson5Formatter::new(config).unwrap();
+
+        let input = "{\n  \"a\":  1,\n  \"b\":  2\n}";
+        let result = formatter
+            .format_lines(input, &[Range { start: 2, end: 3 }])
+            .unwrap();
+        assert_eq!(result, "{\n  \"a\":  1,\n  \"b\": 2\n}");
+
+        let result = formatter.format_lines(input, &[]).unwrap();
+        assert_eq!(result, input);
+    }
+
+    #[test]
+    fn test_format_lines_formats_the_enclosing_node() {
+        let config = Config::default();
+        let mut formatter = MustacheJson5Formatter::new(config).unwrap();
+
+        // A change inside an inline array reformats the member holding it
+        let input = "{\n  \"a\":  [1,2],\n  \"b\":  {\"c\":1,\"d\":2}\n}";
+        let result = formatter
+            .format_lines(input, &[Range { start: 1, end: 2 }])
+            .unwrap();
+        assert_eq!(
+            result,
+            "{\n  \"a\": [1, 2],\n  \"b\":  {\"c\":1,\"d\":2}\n}"
+        );
+
+        // Nested objects are formatted at their own depth
+        let result = formatter
+            .format_lines(input, &[Range { start: 2, end: 3 }])
+            .unwrap();
+        assert_eq!(
+            result,
+            "{\n  \"a\":  [1,2],\n  \"b\": {\n    \"c\": 1,\n    \"d\": 2\n  }\n}"
+        );
+    }
+
     #[test]
     fn test_mustache_section_formatting() {
         let config = Config::default();
@@ -0,0 +1,215 @@
+//! Discover changed templates by asking the `git` binary
+//!
+//! Used to limit formatting to files (and optionally lines) that changed
+//! relative to a revision or the index, so large repositories can adopt the
+//! formatter incrementally.
+
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// What to compare against when looking for changes
+#[derive(Debug, Clone)]
+pub enum DiffTarget {
+    /// Changes in the working tree relative to a revision
+    Revision(String),
+    /// Changes staged in the index relative to `HEAD`
+    Staged,
+}
+
+/// A changed template and the line ranges that changed in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedFile {
+    /// Absolute path to the file in the working tree
+    pub path: PathBuf,
+    /// Zero-based, half-open ranges of added or modified lines
+    pub lines: Vec<Range<usize>>,
+}
+
+/// List changed template files under the repository containing `dir`
+///
+/// Deleted files are skipped; renamed and copied files are reported under
+/// their new path.
+pub fn changed_files(dir: &Path, target: &DiffTarget) -> Result<Vec<ChangedFile>> {
+    let root = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let root = PathBuf::from(root.trim());
+
+    let mut args = vec![
+        "diff",
+        "-U0",
+        "--no-color",
+        "--no-ext-diff",
+        "--diff-filter=ACMR",
+    ];
+    match target {
+        DiffTarget::Revision(revision) => {
+            args.push(revision);
+            args.push("--");
+        }
+        DiffTarget::Staged => args.push("--cached"),
+    }
+
+    let diff = git(&root, &args)?;
+    Ok(parse_diff(&diff)
+        .into_iter()
+        .filter(|file| crate::is_template_file(&file.path))
+        .map(|file| ChangedFile {
+            path: root.join(file.path),
+            lines: file.lines,
+        })
+        .collect())
+}
+
+/// The contents of `path` as staged in the index
+pub fn staged_contents(path: &Path) -> Result<String> {
+    let (dir, name) = split(path)?;
+    git(dir, &["show", &format!(":./{}", name)])
+}
+
+/// Replace the staged contents of `path`, leaving the working tree alone
+pub fn stage_contents(path: &Path, contents: &str) -> Result<()> {
+    let (dir, name) = split(path)?;
+    let entry = git(dir, &["ls-files", "--stage", "--", name])?;
+    let Some(mode) = entry.split_whitespace().next() else {
+        bail!("{} is not in the index", path.display());
+    };
+    let blob = git_with_input(
+        dir,
+        &["hash-object", "-w", "--stdin", "--path", name],
+        contents,
+    )?;
+    let cacheinfo = format!("{},{},{}", mode, blob.trim(), name);
+    git(dir, &["update-index", "--cacheinfo", &cacheinfo])?;
+    Ok(())
+}
+
+/// The directory to run git in for `path`, and the file name within it
+fn split(path: &Path) -> Result<(&Path, &str)> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("Invalid path: {}", path.display()))?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    Ok((dir, name))
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    git_with_input(dir, args, "")
+}
+
+fn git_with_input(dir: &Path, args: &[&str], input: &str) -> Result<String> {
+    let mut child = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run git")?;
+    child
+        .stdin
+        .take()
+        .context("Failed to open git's stdin")?
+        .write_all(input.as_bytes())
+        .context("Failed to write to git")?;
+    let output = child.wait_with_output().context("Failed to run git")?;
+
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    String::from_utf8(output.stdout).context("git produced non-UTF-8 output")
+}
+
+/// Parse `git diff -U0` output into per-file changed line ranges
+fn parse_diff(diff: &str) -> Vec<ChangedFile> {
+    let mut files: Vec<ChangedFile> = Vec::new();
+
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            // `/dev/null` only appears here for deletions, which are filtered out
+            let path = path.strip_prefix("b/").unwrap_or(path);
+            files.push(ChangedFile {
+                path: PathBuf::from(path),
+                lines: Vec::new(),
+            });
+        } else if let Some(hunk) = line.strip_prefix("@@ ") {
+            let (Some(file), Some(range)) = (files.last_mut(), parse_hunk(hunk)) else {
+                continue;
+            };
+            if !range.is_empty() {
+                file.lines.push(range);
+            }
+        }
+    }
+
+    files
+}
+
+/// Parse the new-file side of a hunk header: `-a,b +c,d @@ ...`
+fn parse_hunk(hunk: &str) -> Option<Range<usize>> {
+    let new_side = hunk.split(' ').find_map(|part| part.strip_prefix('+'))?;
+    let (start, count) = match new_side.split_once(',') {
+        Some((start, count)) => (start.parse::<usize>().ok()?, count.parse::<usize>().ok()?),
+        None => (new_side.parse::<usize>().ok()?, 1),
+    };
+
+    // Hunk lines are one-based; a zero count marks a pure deletion
+    let start = start.saturating_sub(1);
+    Some(start..start + count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diff() {
+        let diff = "\
+diff --git a/a.mjson5 b/a.mjson5
+index 1111111..2222222 100644
+--- a/a.mjson5
++++ b/a.mjson5
+@@ -1 +1 @@
+-{}
++{ }
+@@ -5,0 +6,3 @@ context
++x
++y
++z
+@@ -9,2 +11,0 @@
+-gone
+-gone
+diff --git a/new.mjson5 b/new.mjson5
+new file mode 100644
+--- /dev/null
++++ b/new.mjson5
+@@ -0,0 +1,2 @@
++{
++}
+";
+        let files = parse_diff(diff);
+        assert_eq!(
+            files,
+            vec![
+                ChangedFile {
+                    path: PathBuf::from("a.mjson5"),
+                    lines: vec![0..1, 5..8],
+                },
+                ChangedFile {
+                    path: PathBuf::from("new.mjson5"),
+                    lines: vec![Range { start: 0, end: 2 }],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! Install a git pre-commit hook that checks staged templates
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// First line after the shebang, used to recognise hooks we installed
+const HOOK_MARKER: &str = "# Installed by `mustache-json5-fmt hook install`";
+
+/// Options for the generated hook
+#[derive(Debug, Clone, Default)]
+pub struct HookOptions {
+    /// Format the staged versions of templates in the index instead of
+    /// rejecting the commit
+    pub fix: bool,
+
+    /// Replace an existing pre-commit hook that was not installed by us
+    pub force: bool,
+}
+
+/// Write the pre-commit hook for the repository containing `dir`
+///
+/// Returns the path of the installed hook.
+pub fn install(dir: &Path, options: &HookOptions) -> Result<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .current_dir(dir)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "Not a git repository: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let hooks_dir = dir.join(String::from_utf8_lossy(&output.stdout).trim());
+    let hook_path = hooks_dir.join("pre-commit");
+
+    if let Ok(existing) = fs::read_to_string(&hook_path) {
+        if !existing.contains(HOOK_MARKER) && !options.force {
+            bail!(
+                "{} already exists; use --force to replace it",
+                hook_path.display()
+            );
+        }
+    }
+
+    fs::create_dir_all(&hooks_dir)
+        .with_context(|| format!("Failed to create hooks directory: {}", hooks_dir.display()))?;
+    fs::write(&hook_path, hook_script(options))
+        .with_context(|| format!("Failed to write hook: {}", hook_path.display()))?;
+    make_executable(&hook_path)?;
+
+    Ok(hook_path)
+}
+
+/// Render the shell script for the pre-commit hook
+pub fn hook_script(options: &HookOptions) -> String {
+    let mut script = format!(
+        r#"#!/bin/sh
+{HOOK_MARKER}; re-run it to update.
+fmt="${{MUSTACHE_JSON5_FMT:-mustache-json5-fmt}}"
+
+staged=$(git diff --cached --name-only --diff-filter=ACMR -- '*.mjson5' '*.mustache_json5')
+[ -z "$staged" ] && exit 0
+"#
+    );
+
+    if options.fix {
+        script.push_str(
+            r#"
+# Formats the index, so hunks that are not staged stay out of the commit
+"$fmt" fmt --write --staged || exit 1
+"#,
+        );
+    } else {
+        script.push_str(
+            r#"
+if ! "$fmt" check --staged; then
+  echo "Run \`$fmt fmt --write --staged\` and stage the result." >&2
+  exit 1
+fi
+"#,
+        );
+    }
+
+    script
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o111);
+    fs::set_permissions(path, permissions)
+        .with_context(|| format!("Failed to make hook executable: {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_hook_script_modes() {
+        let check = hook_script(&HookOptions::default());
+        assert!(check.contains("check --staged"));
+        assert!(!check.contains("git add"));
+
+        let fix = hook_script(&HookOptions {
+            fix: true,
+            ..HookOptions::default()
+        });
+        assert!(fix.contains("fmt --write --staged"));
+        assert!(!fix.contains("git add"));
+    }
+
+    #[test]
+    fn test_install_refuses_foreign_hook() {
+        let dir = TempDir::new().unwrap();
+        let status = Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(dir.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let hook_path = install(dir.path(), &HookOptions::default()).unwrap();
+        assert!(fs::read_to_string(&hook_path)
+            .unwrap()
+            .contains(HOOK_MARKER));
+
+        // Reinstalling over our own hook is fine
+        install(dir.path(), &HookOptions::default()).unwrap();
+
+        fs::write(&hook_path, "#!/bin/sh\nexit 0\n").unwrap();
+        assert!(install(dir.path(), &HookOptions::default()).is_err());
+
+        let options = HookOptions {
+            force: true,
+            ..HookOptions::default()
+        };
+        install(dir.path(), &options).unwrap();
+        assert!(fs::read_to_string(&hook_path)
+            .unwrap()
+            .contains(HOOK_MARKER));
+    }
+}
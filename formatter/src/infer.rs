@@ -0,0 +1,222 @@
+//! Infer a configuration from the style of existing templates
+//!
+//! Each option is decided by majority over every template seen. Options whose
+//! usage is mixed fall back to `Preserve` where the config allows it, so the
+//! first formatting run changes as little as possible.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use tree_sitter::Node;
+
+use crate::config::{Config, MustacheSpacing, QuoteStyle, TrailingCommaStyle};
+use crate::inspect;
+
+/// Tally of style choices observed across templates
+#[derive(Debug, Default, Clone)]
+pub struct StyleStats {
+    /// Indented lines starting with a tab / with spaces
+    pub tab_lines: usize,
+    pub space_lines: usize,
+    /// How often each increase in leading spaces between consecutive lines occurs
+    pub indent_steps: HashMap<usize, usize>,
+    pub double_quoted: usize,
+    pub single_quoted: usize,
+    /// Multiline objects and arrays ending with / without a trailing comma
+    pub trailing_comma: usize,
+    pub no_trailing_comma: usize,
+    /// Mustache tags with / without a space after `{{` and before `}}`
+    pub space_after_open: usize,
+    pub tight_open: usize,
+    pub space_before_close: usize,
+    pub tight_close: usize,
+    /// Mustache tags with / without a space after their operator (`#`, `^`, ...)
+    pub space_around_operator: usize,
+    pub tight_operator: usize,
+}
+
+impl StyleStats {
+    /// Record the style of one template
+    pub fn add_source(&mut self, source: &str) -> Result<()> {
+        self.add_indentation(source);
+
+        let tree = inspect::parse(source)?;
+        self.add_node(tree.root_node(), source);
+        Ok(())
+    }
+
+    fn add_indentation(&mut self, source: &str) {
+        let mut previous_indent = 0;
+
+        for line in source.lines().filter(|line| !line.trim().is_empty()) {
+            if line.starts_with('\t') {
+                self.tab_lines += 1;
+                continue;
+            }
+
+            let indent = line.len() - line.trim_start_matches(' ').len();
+            if indent > 0 {
+                self.space_lines += 1;
+            }
+            if indent > previous_indent {
+                *self
+                    .indent_steps
+                    .entry(indent - previous_indent)
+                    .or_default() += 1;
+            }
+            previous_indent = indent;
+        }
+    }
+
+    fn add_node(&mut self, node: Node, source: &str) {
+        let text = &source[node.byte_range()];
+
+        match node.kind() {
+            "string" if text.starts_with('\'') => self.single_quoted += 1,
+            "string" => self.double_quoted += 1,
+            "object" | "array" if node.start_position().row != node.end_position().row => {
+                let closing = node.child(node.child_count().saturating_sub(1));
+                let last_item = closing.and_then(|closing| closing.prev_sibling());
+                if last_item.is_some_and(|item| item.kind() == ",") {
+                    self.trailing_comma += 1;
+                } else {
+                    self.no_trailing_comma += 1;
+                }
+            }
+            "mustache_interpolation"
+            | "mustache_unescaped"
+            | "mustache_partial"
+            | "mustache_section_begin"
+            | "mustache_section_end"
+            | "mustache_inverted_section_begin"
+            | "mustache_inverted_section_end" => self.add_mustache_tag(node, source),
+            _ => {}
+        }
+
+        for child in node.named_children(&mut node.walk()) {
+            self.add_node(child, source);
+        }
+    }
+
+    fn add_mustache_tag(&mut self, node: Node, source: &str) {
+        let text = &source[node.byte_range()];
+        let Some(inner) = text
+            .strip_prefix("{{")
+            .and_then(|inner| inner.strip_suffix("}}"))
+        else {
+            return;
+        };
+
+        if inner.starts_with(char::is_whitespace) {
+            self.space_after_open += 1;
+        } else {
+            self.tight_open += 1;
+        }
+
+        if inner.ends_with(char::is_whitespace) {
+            self.space_before_close += 1;
+        } else {
+            self.tight_close += 1;
+        }
+
+        let operator = node
+            .children(&mut node.walk())
+            .find(|child| matches!(child.kind(), "#" | "^" | "/" | ">" | "&"));
+        if let Some(operator) = operator {
+            if source[operator.end_byte()..].starts_with(char::is_whitespace) {
+                self.space_around_operator += 1;
+            } else {
+                self.tight_operator += 1;
+            }
+        }
+    }
+
+    /// Build the configuration that best matches the recorded style
+    pub fn to_config(&self) -> Config {
+        let use_tabs = self.tab_lines > self.space_lines;
+        let indent_size = self
+            .indent_steps
+            .iter()
+            .max_by_key(|(step, count)| (**count, std::cmp::Reverse(**step)))
+            .map(|(step, _)| *step)
+            .unwrap_or(Config::default().indent_size);
+
+        let quote_style = match (self.double_quoted, self.single_quoted) {
+            (0, 0) => QuoteStyle::Preserve,
+            (_, 0) => QuoteStyle::Double,
+            (0, _) => QuoteStyle::Single,
+            _ => QuoteStyle::Preserve,
+        };
+
+        let trailing_commas = match (self.trailing_comma, self.no_trailing_comma) {
+            (0, 0) => TrailingCommaStyle::Preserve,
+            (_, 0) => TrailingCommaStyle::Always,
+            (0, _) => TrailingCommaStyle::Never,
+            _ => TrailingCommaStyle::Preserve,
+        };
+
+        Config {
+            indent_size,
+            tab_width: if use_tabs {
+                indent_size
+            } else {
+                Config::default().tab_width
+            },
+            use_tabs,
+            quote_style,
+            trailing_commas,
+            mustache_spacing: MustacheSpacing {
+                after_open: self.space_after_open > self.tight_open,
+                before_close: self.space_before_close > self.tight_close,
+                around_operators: self.space_around_operator > self.tight_operator,
+            },
+            ..Config::default()
+        }
+    }
+}
+
+/// Infer a configuration from the given template sources
+pub fn infer_config<S: AsRef<str>>(sources: &[S]) -> Result<Config> {
+    let mut stats = StyleStats::default();
+    for source in sources {
+        stats.add_source(source.as_ref())?;
+    }
+    Ok(stats.to_config())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_indentation() {
+        let config = infer_config(&["{\n    \"a\": {\n        \"b\": 1\n    }\n}"]).unwrap();
+        assert_eq!(config.indent_size, 4);
+        assert!(!config.use_tabs);
+
+        let config = infer_config(&["{\n\t\"a\": {\n\t\t\"b\": 1\n\t}\n}"]).unwrap();
+        assert!(config.use_tabs);
+    }
+
+    #[test]
+    fn test_infer_quotes_and_commas() {
+        let config = infer_config(&["{\n  'a': 'x',\n  'b': 'y',\n}"]).unwrap();
+        assert!(matches!(config.quote_style, QuoteStyle::Single));
+        assert!(matches!(config.trailing_commas, TrailingCommaStyle::Always));
+
+        let config = infer_config(&["{\n  'a': \"x\"\n}"]).unwrap();
+        assert!(matches!(config.quote_style, QuoteStyle::Preserve));
+        assert!(matches!(config.trailing_commas, TrailingCommaStyle::Never));
+    }
+
+    #[test]
+    fn test_infer_mustache_spacing() {
+        let config = infer_config(&["{{ # users }}{{ name }}{{ / users }}"]).unwrap();
+        assert!(config.mustache_spacing.after_open);
+        assert!(config.mustache_spacing.before_close);
+        assert!(config.mustache_spacing.around_operators);
+
+        let config = infer_config(&["{{#users}}{{name}}{{/users}}"]).unwrap();
+        assert!(!config.mustache_spacing.after_open);
+        assert!(!config.mustache_spacing.around_operators);
+    }
+}
@@ -0,0 +1,291 @@
+//! Render syntax trees and query captures for debugging templates
+//!
+//! Only named nodes are shown, plus `MISSING` nodes inserted by error
+//! recovery. `ERROR` and `MISSING` nodes can be highlighted so problems stand
+//! out in large trees.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use std::fmt::Write;
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{Node, Parser, Query, QueryCursor, Tree};
+use tree_sitter_mustache_json5::LANGUAGE;
+
+/// Output format for a syntax tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeFormat {
+    /// S-expression with positions, like `tree-sitter parse`
+    Sexp,
+    /// Indented outline with field names and leaf text
+    Outline,
+    /// Nested JSON objects
+    Json,
+}
+
+/// A syntax tree node in serialisable form
+#[derive(Debug, Clone, Serialize)]
+pub struct SyntaxNode {
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<&'static str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub error: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub missing: bool,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start: Position,
+    pub end: Position,
+    /// Source text, for nodes without visible children
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<SyntaxNode>,
+}
+
+/// Zero-based row and column
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Position {
+    pub row: usize,
+    pub column: usize,
+}
+
+/// Parse `source` with the mustache_json5 grammar
+pub fn parse(source: &str) -> Result<Tree> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&LANGUAGE.into())
+        .context("Failed to set language for parser")?;
+    parser
+        .parse(source, None)
+        .context("Failed to parse source code")
+}
+
+/// Build the serialisable tree rooted at `tree`'s root node
+pub fn syntax_tree(tree: &Tree, source: &str) -> SyntaxNode {
+    build_node(tree.root_node(), None, source)
+}
+
+fn build_node(node: Node, field: Option<&'static str>, source: &str) -> SyntaxNode {
+    let mut children = Vec::new();
+    let mut cursor = node.walk();
+    if cursor.goto_first_child() {
+        loop {
+            let child = cursor.node();
+            if child.is_named() || child.is_missing() {
+                children.push(build_node(child, cursor.field_name(), source));
+            }
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+
+    let text = children
+        .is_empty()
+        .then(|| source[node.byte_range()].to_string());
+
+    SyntaxNode {
+        kind: node.kind().to_string(),
+        field,
+        error: node.is_error(),
+        missing: node.is_missing(),
+        start_byte: node.start_byte(),
+        end_byte: node.end_byte(),
+        start: Position {
+            row: node.start_position().row,
+            column: node.start_position().column,
+        },
+        end: Position {
+            row: node.end_position().row,
+            column: node.end_position().column,
+        },
+        text,
+        children,
+    }
+}
+
+/// Render a syntax tree in the requested format
+///
+/// With `highlight`, `ERROR` and `MISSING` nodes are coloured in the text
+/// formats; JSON output is never coloured.
+pub fn render_tree(
+    tree: &Tree,
+    source: &str,
+    format: TreeFormat,
+    highlight: bool,
+) -> Result<String> {
+    let root = syntax_tree(tree, source);
+    let mut output = String::new();
+
+    match format {
+        TreeFormat::Sexp => {
+            write_sexp(&mut output, &root, 0, highlight);
+            output.push('\n');
+        }
+        TreeFormat::Outline => write_outline(&mut output, &root, 0, highlight),
+        TreeFormat::Json => {
+            output = serde_json::to_string_pretty(&root).context("Failed to serialize tree")?;
+            output.push('\n');
+        }
+    }
+
+    Ok(output)
+}
+
+fn node_label(node: &SyntaxNode, highlight: bool) -> String {
+    let label = if node.missing {
+        format!("MISSING {}", node.kind)
+    } else {
+        node.kind.clone()
+    };
+
+    if highlight && (node.error || node.missing) {
+        label.red().bold().to_string()
+    } else {
+        label
+    }
+}
+
+fn write_sexp(output: &mut String, node: &SyntaxNode, depth: usize, highlight: bool) {
+    if depth > 0 {
+        output.push('\n');
+    }
+    output.push_str(&"  ".repeat(depth));
+    if let Some(field) = node.field {
+        let _ = write!(output, "{}: ", field);
+    }
+    let _ = write!(
+        output,
+        "({} [{}, {}] - [{}, {}]",
+        node_label(node, highlight),
+        node.start.row,
+        node.start.column,
+        node.end.row,
+        node.end.column
+    );
+    for child in &node.children {
+        write_sexp(output, child, depth + 1, highlight);
+    }
+    output.push(')');
+}
+
+fn write_outline(output: &mut String, node: &SyntaxNode, depth: usize, highlight: bool) {
+    output.push_str(&"  ".repeat(depth));
+    if let Some(field) = node.field {
+        let _ = write!(output, "{}: ", field);
+    }
+    let _ = write!(
+        output,
+        "{} {}:{}-{}:{}",
+        node_label(node, highlight),
+        node.start.row,
+        node.start.column,
+        node.end.row,
+        node.end.column
+    );
+    if let Some(text) = &node.text {
+        let _ = write!(output, " {:?}", text);
+    }
+    output.push('\n');
+
+    for child in &node.children {
+        write_outline(output, child, depth + 1, highlight);
+    }
+}
+
+/// Collect `ERROR` and `MISSING` nodes in document order
+pub fn problem_nodes(tree: &Tree) -> Vec<Node<'_>> {
+    let mut problems = Vec::new();
+    let mut stack = vec![tree.root_node()];
+
+    while let Some(node) = stack.pop() {
+        if node.is_error() || node.is_missing() {
+            problems.push(node);
+        }
+        if node.has_error() {
+            let mut cursor = node.walk();
+            let children: Vec<Node> = node.children(&mut cursor).collect();
+            stack.extend(children.into_iter().rev());
+        }
+    }
+
+    problems
+}
+
+/// Run `query_source` against `tree` and render one line per capture
+pub fn render_captures(tree: &Tree, source: &str, query_source: &str) -> Result<String> {
+    let query = Query::new(&LANGUAGE.into(), query_source).context("Failed to parse query")?;
+    let mut cursor = QueryCursor::new();
+    let mut captures = cursor.captures(&query, tree.root_node(), source.as_bytes());
+
+    let mut output = String::new();
+    while let Some((query_match, capture_index)) = captures.next() {
+        let capture = query_match.captures[*capture_index];
+        let node = capture.node;
+        let _ = writeln!(
+            output,
+            "pattern {}, @{}: {} [{}, {}] - [{}, {}] {:?}",
+            query_match.pattern_index,
+            query.capture_names()[capture.index as usize],
+            node.kind(),
+            node.start_position().row,
+            node.start_position().column,
+            node.end_position().row,
+            node.end_position().column,
+            &source[node.byte_range()]
+        );
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sexp_includes_fields_and_positions() {
+        let source = r#"{"a": 1}"#;
+        let tree = parse(source).unwrap();
+        let output = render_tree(&tree, source, TreeFormat::Sexp, false).unwrap();
+
+        assert!(output.starts_with("(source_file [0, 0] - [0, 8]"));
+        assert!(output.contains("name: (name [0, 1] - [0, 4]"));
+        assert!(output.contains("value: (number [0, 6] - [0, 7])"));
+    }
+
+    #[test]
+    fn test_json_output() {
+        let source = r#"{"a": {{value}}}"#;
+        let tree = parse(source).unwrap();
+        let output = render_tree(&tree, source, TreeFormat::Json, false).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(json["kind"], "source_file");
+        assert_eq!(json["start_byte"], 0);
+        assert_eq!(json["end"]["column"], 16);
+        assert!(output.contains(r#""kind": "mustache_interpolation""#));
+        assert!(output.contains(r#""field": "value""#));
+    }
+
+    #[test]
+    fn test_problem_nodes() {
+        let tree = parse(r#"{"a": 1"#).unwrap();
+        assert!(!problem_nodes(&tree).is_empty());
+
+        let tree = parse(r#"{"a": 1}"#).unwrap();
+        assert!(problem_nodes(&tree).is_empty());
+    }
+
+    #[test]
+    fn test_render_captures() {
+        let source = r#"{"a": 1, "b": 2}"#;
+        let tree = parse(source).unwrap();
+        let output = render_captures(&tree, source, "(member name: (name) @key)").unwrap();
+
+        assert_eq!(output.lines().count(), 2);
+        assert!(output.contains(r#"@key: name [0, 1] - [0, 4] "\"a\"""#));
+    }
+}
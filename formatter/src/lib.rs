@@ -36,17 +36,38 @@
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 
+pub mod builder;
+pub mod cache;
 pub mod config;
+pub mod document;
 pub mod formatter;
+pub mod git;
+pub mod hook;
+pub mod infer;
+pub mod inspect;
+pub mod lint;
+pub mod watch;
 
 pub use config::{
-    CommentHandling, Config, MustacheIndentStyle, MustacheSpacing, QuoteStyle, TrailingCommaStyle,
+    CommentHandling, Config, LintConfig, MustacheIndentStyle, MustacheSpacing, QuoteStyle,
+    RuleConfig, RuleLevel, TrailingCommaStyle,
 };
+pub use document::{Document, Node, NodeKind};
 pub use formatter::MustacheJson5Formatter;
 
 /// Result type used throughout the crate
 pub type Result<T> = anyhow::Result<T>;
 
+/// File extensions recognised as mustache_json5 templates
+pub const TEMPLATE_EXTENSIONS: &[&str] = &["mustache_json5", "mjson5"];
+
+/// Check whether a path has one of the [`TEMPLATE_EXTENSIONS`]
+pub fn is_template_file(path: &std::path::Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| TEMPLATE_EXTENSIONS.contains(&extension))
+}
+
 /// Format a string of mustache_json5 content with default configuration
 ///
 /// This is a convenience function for simple formatting needs.
@@ -154,8 +175,10 @@ mod tests {
 
     #[test]
     fn test_format_string_with_config() {
-        let mut config = Config::default();
-        config.indent_size = 4;
+        let config = Config {
+            indent_size: 4,
+            ..Config::default()
+        };
 
         let input = r#"{"name":"test"}"#;
         let result = format_string_with_config(input, config);
@@ -0,0 +1,768 @@
+//! Lint templates with `queries/lints.scm`
+//!
+//! Each capture named `@error.<rule>` or `@warning.<rule>` in the query
+//! reports a diagnostic, with dots in the rest of the name turned into
+//! dashes: `@warning.unsafe.unescaped` is the `unsafe-unescaped` rule.
+//! Other captures, such as `@start.tag`, are only used by predicates.
+//!
+//! Besides the predicates tree-sitter evaluates itself (`#eq?`, `#match?`,
+//! `#any-of?` and their negations), queries can use `#has-ancestor?` and
+//! `#not-has-ancestor?`, which take a capture and one or more node kinds.
+//!
+//! Checks that need to follow scopes or compare nodes are [`Rule`]s written
+//! in Rust; the built-in ones are in [`rules`].
+//!
+//! Diagnostics with a mechanical fix carry the [`Edit`]s for it, which
+//! [`Linter::fix`] applies.
+//!
+//! Rules can be turned off or have their severity changed in the `lint`
+//! section of the configuration file, and silenced for a line or a file with
+//! comments; see [`suppress`].
+
+pub mod rules;
+pub mod suppress;
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{Node, Query, QueryCursor, QueryMatch, QueryPredicate, QueryPredicateArg, Tree};
+use tree_sitter_mustache_json5::{LANGUAGE, LINTS_QUERY};
+
+use crate::config::{LintConfig, RuleLevel};
+use crate::inspect;
+use suppress::Suppressions;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found by a lint rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// Rule ID, e.g. `mismatched-section`
+    pub rule: String,
+    pub severity: Severity,
+    pub message: String,
+    /// Byte range of the node the rule matched
+    pub span: Range<usize>,
+    /// One-based line of the start of `span`
+    pub line: usize,
+    /// One-based column of the start of `span`
+    pub column: usize,
+    /// Edits that fix the problem, if it has a mechanical fix
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fix: Vec<Edit>,
+}
+
+/// Replace the bytes in `span` with `replacement`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Edit {
+    pub span: Range<usize>,
+    pub replacement: String,
+}
+
+impl Edit {
+    /// Replace `node`
+    pub fn replace(node: Node, replacement: impl Into<String>) -> Self {
+        Edit {
+            span: node.byte_range(),
+            replacement: replacement.into(),
+        }
+    }
+
+    /// Remove the bytes in `span`
+    pub fn delete(span: Range<usize>) -> Self {
+        Edit {
+            span,
+            replacement: String::new(),
+        }
+    }
+}
+
+/// The result of [`Linter::fix`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixed {
+    /// The fixed template
+    pub source: String,
+    /// Number of diagnostics fixed
+    pub fixed: usize,
+    /// Diagnostics left in the fixed template
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}[{}]: {}",
+            self.line, self.column, self.severity, self.rule, self.message
+        )
+    }
+}
+
+impl Diagnostic {
+    fn new(rule: &str, severity: Severity, node: Node, message: String) -> Self {
+        Diagnostic {
+            rule: rule.to_string(),
+            severity,
+            message,
+            span: node.byte_range(),
+            line: node.start_position().row + 1,
+            column: node.start_position().column + 1,
+            fix: Vec::new(),
+        }
+    }
+}
+
+/// A lint check written in Rust
+pub trait Rule {
+    /// Rule ID, e.g. `duplicate-key`
+    fn id(&self) -> &'static str;
+
+    /// Severity of the diagnostics the rule reports
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    /// Report problems in the tree under `root`
+    fn check(&self, root: Node, reporter: &mut Reporter);
+
+    /// Apply rule-specific options from the configuration file
+    fn configure(&mut self, options: &serde_json::Map<String, serde_json::Value>) -> Result<()> {
+        match options.keys().next() {
+            Some(option) => bail!("Unknown option `{}` for lint rule `{}`", option, self.id()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Collects the diagnostics of one [`Rule`]
+pub struct Reporter<'a> {
+    rule: &'static str,
+    severity: Severity,
+    source: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Reporter<'a> {
+    /// The template being linted
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// The source text of `node`
+    pub fn text(&self, node: Node) -> &'a str {
+        &self.source[node.byte_range()]
+    }
+
+    /// Report a problem at `node`
+    pub fn report(&mut self, node: Node, message: impl Into<String>) {
+        self.report_with_fix(node, message, Vec::new());
+    }
+
+    /// Report a problem at `node` that `fix` corrects
+    pub fn report_with_fix(&mut self, node: Node, message: impl Into<String>, fix: Vec<Edit>) {
+        let mut diagnostic = Diagnostic::new(self.rule, self.severity, node, message.into());
+        diagnostic.fix = fix;
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+/// A lint query, with the rule and severity for each capture, `None` for
+/// helper captures
+struct QueryRules {
+    query: Query,
+    captures: Vec<Option<(String, Severity)>>,
+}
+
+/// Runs a lint query and [`Rule`]s over templates
+pub struct Linter {
+    query: Option<QueryRules>,
+    rules: Vec<Box<dyn Rule>>,
+    /// Levels from the configuration, overriding each rule's own severity
+    levels: HashMap<String, RuleLevel>,
+    /// Whether the template must render plain JSON, so trailing commas are
+    /// errors with a fix rather than warnings
+    forbid_trailing_commas: bool,
+}
+
+impl Linter {
+    /// A linter for the bundled `queries/lints.scm` and [`rules::default_rules`]
+    pub fn new() -> Result<Self> {
+        Self::with_query(LINTS_QUERY)
+    }
+
+    /// A linter for a custom query, using the same capture naming, and
+    /// [`rules::default_rules`]
+    pub fn with_query(query_source: &str) -> Result<Self> {
+        Ok(Linter {
+            query: Some(QueryRules::new(query_source)?),
+            rules: rules::default_rules(),
+            levels: HashMap::new(),
+            forbid_trailing_commas: false,
+        })
+    }
+
+    /// A linter without a query or rules
+    pub fn empty() -> Self {
+        Linter {
+            query: None,
+            rules: Vec::new(),
+            levels: HashMap::new(),
+            forbid_trailing_commas: false,
+        }
+    }
+
+    /// Apply the `lint` section of the configuration file
+    pub fn configure(&mut self, config: &LintConfig) -> Result<()> {
+        for (id, rule_config) in &config.rules {
+            let in_query = self
+                .query
+                .as_ref()
+                .is_some_and(|query| query.captures.iter().flatten().any(|(rule, _)| rule == id));
+            let options = rule_config.options();
+            match self.rules.iter_mut().find(|rule| rule.id() == id) {
+                Some(rule) => rule
+                    .configure(&options)
+                    .with_context(|| format!("Invalid options for lint rule `{}`", id))?,
+                None if in_query => {
+                    if let Some(option) = options.keys().next() {
+                        bail!("Unknown option `{}` for lint rule `{}`", option, id);
+                    }
+                }
+                None => bail!("Unknown lint rule `{}`", id),
+            }
+            if let Some(level) = rule_config.level() {
+                self.levels.insert(id.clone(), level);
+            }
+        }
+        Ok(())
+    }
+
+    /// Treat trailing commas as errors and offer to delete them, for
+    /// configurations with `trailing_commas: Never`
+    pub fn forbid_trailing_commas(&mut self, forbid: bool) {
+        self.forbid_trailing_commas = forbid;
+    }
+
+    /// Add a rule, replacing any rule with the same ID
+    pub fn set_rule(&mut self, rule: Box<dyn Rule>) {
+        self.rules.retain(|existing| existing.id() != rule.id());
+        self.rules.push(rule);
+    }
+
+    /// Lint `source`, returning diagnostics in document order
+    pub fn lint(&self, source: &str) -> Result<Vec<Diagnostic>> {
+        let tree = inspect::parse(source)?;
+        Ok(self.lint_tree(&tree, source))
+    }
+
+    /// Lint an already parsed template
+    pub fn lint_tree(&self, tree: &Tree, source: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = match &self.query {
+            Some(query) => query.lint(tree, source),
+            None => Vec::new(),
+        };
+        if self.forbid_trailing_commas {
+            for diagnostic in &mut diagnostics {
+                if diagnostic.rule == "trailing-comma" {
+                    diagnostic.severity = Severity::Error;
+                    diagnostic.fix = vec![Edit::delete(diagnostic.span.clone())];
+                }
+            }
+        }
+        for rule in &self.rules {
+            if self.levels.get(rule.id()) == Some(&RuleLevel::Off) {
+                continue;
+            }
+            let mut reporter = Reporter {
+                rule: rule.id(),
+                severity: rule.severity(),
+                source,
+                diagnostics: Vec::new(),
+            };
+            rule.check(tree.root_node(), &mut reporter);
+            diagnostics.extend(reporter.diagnostics);
+        }
+
+        let suppressions = Suppressions::collect(tree.root_node(), source);
+        diagnostics.retain_mut(|diagnostic| {
+            diagnostic.severity = match self.levels.get(&diagnostic.rule) {
+                Some(RuleLevel::Off) => return false,
+                Some(RuleLevel::Warn) => Severity::Warning,
+                Some(RuleLevel::Error) => Severity::Error,
+                None => diagnostic.severity,
+            };
+            !suppressions.suppresses(diagnostic)
+        });
+        diagnostics.sort_by(|a, b| {
+            (a.span.start, a.span.end, &a.rule).cmp(&(b.span.start, b.span.end, &b.rule))
+        });
+        diagnostics.dedup();
+        diagnostics
+    }
+
+    /// Apply the fixes of `source`'s diagnostics
+    ///
+    /// Fixes are applied in batches. A fix whose edits overlap an earlier one
+    /// waits for the next batch, and the template is linted again between
+    /// batches, so each fix applies to the text it was computed for. A batch
+    /// that would break a template which parsed cleanly is not applied.
+    pub fn fix(&self, source: &str) -> Result<Fixed> {
+        let mut source = source.to_string();
+        let mut fixed = 0;
+        for _ in 0..MAX_FIX_BATCHES {
+            let tree = inspect::parse(&source)?;
+            let diagnostics = self.lint_tree(&tree, &source);
+
+            let mut edits: Vec<&Edit> = Vec::new();
+            let mut batch = 0;
+            for diagnostic in &diagnostics {
+                if diagnostic.fix.is_empty() || overlaps(&diagnostic.fix) {
+                    continue;
+                }
+                if diagnostic
+                    .fix
+                    .iter()
+                    .any(|edit| edits.iter().any(|applied| intersects(edit, applied)))
+                {
+                    continue;
+                }
+                edits.extend(&diagnostic.fix);
+                batch += 1;
+            }
+            if batch == 0 {
+                break;
+            }
+
+            edits.sort_by_key(|edit| edit.span.start);
+            let mut result = source.clone();
+            for edit in edits.iter().rev() {
+                result.replace_range(edit.span.clone(), &edit.replacement);
+            }
+            if !tree.root_node().has_error() && inspect::parse(&result)?.root_node().has_error() {
+                break;
+            }
+            source = result;
+            fixed += batch;
+        }
+
+        let diagnostics = self.lint(&source)?;
+        Ok(Fixed {
+            source,
+            fixed,
+            diagnostics,
+        })
+    }
+}
+
+/// Upper bound on lint-and-fix rounds, in case fixes keep producing new ones
+const MAX_FIX_BATCHES: usize = 10;
+
+/// Whether any two of `edits` touch the same bytes
+fn overlaps(edits: &[Edit]) -> bool {
+    edits
+        .iter()
+        .enumerate()
+        .any(|(i, a)| edits[i + 1..].iter().any(|b| intersects(a, b)))
+}
+
+fn intersects(a: &Edit, b: &Edit) -> bool {
+    a.span.start < b.span.end && b.span.start < a.span.end
+}
+
+impl fmt::Debug for Linter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Linter")
+            .field("query", &self.query.as_ref().map(|query| &query.query))
+            .field(
+                "rules",
+                &self.rules.iter().map(|rule| rule.id()).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl QueryRules {
+    fn new(query_source: &str) -> Result<Self> {
+        let query =
+            Query::new(&LANGUAGE.into(), query_source).context("Failed to parse lint query")?;
+        for pattern in 0..query.pattern_count() {
+            for predicate in query.general_predicates(pattern) {
+                check_predicate(predicate)?;
+            }
+        }
+
+        let captures = query
+            .capture_names()
+            .iter()
+            .map(|name| {
+                let (severity, rule) = if let Some(rule) = name.strip_prefix("error.") {
+                    (Severity::Error, rule)
+                } else if let Some(rule) = name.strip_prefix("warning.") {
+                    (Severity::Warning, rule)
+                } else {
+                    return None;
+                };
+                Some((rule.replace('.', "-"), severity))
+            })
+            .collect();
+
+        Ok(QueryRules { query, captures })
+    }
+
+    fn lint(&self, tree: &Tree, source: &str) -> Vec<Diagnostic> {
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&self.query, tree.root_node(), source.as_bytes());
+
+        let mut diagnostics = Vec::new();
+        while let Some(query_match) = matches.next() {
+            let predicates = self.query.general_predicates(query_match.pattern_index);
+            if !predicates
+                .iter()
+                .all(|predicate| satisfies(predicate, query_match))
+            {
+                continue;
+            }
+
+            for capture in query_match.captures {
+                let Some((rule, severity)) = &self.captures[capture.index as usize] else {
+                    continue;
+                };
+                diagnostics.push(diagnostic(rule, *severity, capture.node, source));
+            }
+        }
+        diagnostics
+    }
+}
+
+/// Reject predicates the linter cannot evaluate, rather than ignore them
+fn check_predicate(predicate: &QueryPredicate) -> Result<()> {
+    match predicate.operator.as_ref() {
+        "has-ancestor?" | "not-has-ancestor?" => {
+            let valid = matches!(predicate.args.first(), Some(QueryPredicateArg::Capture(_)))
+                && predicate.args.len() > 1
+                && predicate.args[1..]
+                    .iter()
+                    .all(|arg| matches!(arg, QueryPredicateArg::String(_)));
+            if !valid {
+                bail!(
+                    "`#{}` expects a capture followed by node kinds",
+                    predicate.operator
+                );
+            }
+            Ok(())
+        }
+        operator => bail!("Unsupported predicate `#{}` in lint query", operator),
+    }
+}
+
+fn satisfies(predicate: &QueryPredicate, query_match: &QueryMatch) -> bool {
+    let Some(QueryPredicateArg::Capture(index)) = predicate.args.first() else {
+        return false;
+    };
+    let kinds: Vec<&str> = predicate.args[1..]
+        .iter()
+        .filter_map(|arg| match arg {
+            QueryPredicateArg::String(kind) => Some(kind.as_ref()),
+            QueryPredicateArg::Capture(_) => None,
+        })
+        .collect();
+    let has_ancestor = query_match
+        .nodes_for_capture_index(*index)
+        .any(|node| has_ancestor(node, &kinds));
+
+    match predicate.operator.as_ref() {
+        "has-ancestor?" => has_ancestor,
+        "not-has-ancestor?" => !has_ancestor,
+        _ => false,
+    }
+}
+
+fn has_ancestor(node: Node, kinds: &[&str]) -> bool {
+    let mut parent = node.parent();
+    while let Some(ancestor) = parent {
+        if kinds.contains(&ancestor.kind()) {
+            return true;
+        }
+        parent = ancestor.parent();
+    }
+    false
+}
+
+fn diagnostic(rule: &str, severity: Severity, node: Node, source: &str) -> Diagnostic {
+    let text = source[node.byte_range()].lines().next().unwrap_or("");
+    let text = if text.chars().count() > 40 {
+        format!("{}...", text.chars().take(40).collect::<String>())
+    } else {
+        text.to_string()
+    };
+    Diagnostic::new(rule, severity, node, message(rule, &text))
+}
+
+/// Messages for the rules in the bundled query
+fn message(rule: &str, text: &str) -> String {
+    match rule {
+        "unclosed-section" => format!("Section `{}` is never closed", text),
+        "empty-expression" => "Empty mustache tag".to_string(),
+        "deprecated" => format!("`{}` uses deprecated syntax", text),
+        "trailing-comma" => "Trailing comma".to_string(),
+        "invalid-params" => format!("Malformed section parameters `{}`", text),
+        "invalid-path" => format!("Invalid path `{}`", text),
+        "unclosed-delimiter" => "Unclosed `{{`".to_string(),
+        "inconsistent-quotes" => "Object mixes single- and double-quoted keys".to_string(),
+        "invalid-identifier" => format!("Identifier `{}` starts with a digit", text),
+        "debug-comment" => format!("Leftover marker in comment `{}`", text.trim()),
+        "invalid-partial" => format!("Invalid partial name `{}`", text),
+        "long-expression" => "Expression is longer than 50 characters".to_string(),
+        "reserved-keyword" => format!("`{}` is a reserved helper name", text),
+        "empty-object" => "Empty object".to_string(),
+        "empty-array" => "Empty array".to_string(),
+        rule => format!("`{}` matches {}", text, rule.replace('-', " ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.rule.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_capture_names_map_to_rules() {
+        let linter = Linter::with_query(
+            r#"((mustache_unescaped) @warning.raw.output)
+               ((number) @value (#eq? @value "0")) @error.zero"#,
+        )
+        .unwrap();
+        let diagnostics = linter.lint(r#"[{{&body}}, 0, 1]"#).unwrap();
+
+        assert_eq!(
+            rules(&diagnostics),
+            ["raw-output", "unsafe-unescaped", "zero"]
+        );
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[2].severity, Severity::Error);
+        assert_eq!(diagnostics[2].span, 12..13);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "1:2: warning[raw-output]: `{{&body}}` matches raw output"
+        );
+    }
+
+    #[test]
+    fn test_ancestor_predicates() {
+        let linter = Linter::with_query(
+            r#"((mustache_interpolation) @warning.outside
+                 (#not-has-ancestor? @warning.outside mustache_section mustache_inverted_section))
+               ((mustache_interpolation) @warning.inside
+                 (#has-ancestor? @warning.inside mustache_section))"#,
+        )
+        .unwrap();
+        let diagnostics = linter.lint(r#"[{{a}}, {{#s}}{{b}}{{/s}}]"#).unwrap();
+
+        assert_eq!(rules(&diagnostics), ["outside", "inside"]);
+        assert_eq!(diagnostics[1].span, 14..19);
+    }
+
+    #[test]
+    fn test_unknown_predicates_are_rejected() {
+        let error =
+            Linter::with_query(r#"((object) @warning.x (#is-big? @warning.x))"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported predicate `#is-big?` in lint query"
+        );
+    }
+
+    #[test]
+    fn test_bundled_query() {
+        let linter = Linter::new().unwrap();
+        let diagnostics = linter
+            .lint(r#"[{{#items}}{{{html}}}{{/other}}, {{! TODO remove }}]"#)
+            .unwrap();
+        let found = rules(&diagnostics);
+
+        assert!(found.contains(&"mismatched-section"));
+        assert!(found.contains(&"unsafe-unescaped"));
+        assert!(found.contains(&"debug-comment"));
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| !diagnostic.rule.contains('.')));
+    }
+
+    #[test]
+    fn test_trailing_comma_is_reported_at_the_comma() {
+        let linter = Linter::new().unwrap();
+        let trailing = |source: &str| -> Vec<usize> {
+            linter
+                .lint(source)
+                .unwrap()
+                .into_iter()
+                .filter(|diagnostic| diagnostic.rule == "trailing-comma")
+                .map(|diagnostic| diagnostic.span.start)
+                .collect()
+        };
+
+        assert_eq!(trailing("[1, 2,]"), [5]);
+        assert_eq!(trailing("{a: 1,}"), [5]);
+        assert!(trailing("[1, 2]").is_empty());
+        assert!(trailing("{a: 1, b: 2}").is_empty());
+    }
+
+    #[test]
+    fn test_trailing_commas_are_only_fixed_when_forbidden() {
+        let mut linter = Linter::new().unwrap();
+        let diagnostics = linter.lint("[1,]").unwrap();
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0].fix.is_empty());
+        assert_eq!(linter.fix("[1,]").unwrap().source, "[1,]");
+
+        linter.forbid_trailing_commas(true);
+        let diagnostics = linter.lint("[1,]").unwrap();
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].fix, [Edit::delete(2..3)]);
+        assert_eq!(linter.fix("[1,]").unwrap().source, "[1]");
+    }
+
+    #[test]
+    fn test_fix() {
+        let mut linter = Linter::new().unwrap();
+        linter.forbid_trailing_commas(true);
+        let fixed = linter
+            .fix("{\n  \"a\": 1,\n  \"a\": 2,\n  \"b\": [{{#items}}{{{html}}}{{/item}}, 1,],\n  a: 3\n}")
+            .unwrap();
+
+        assert_eq!(
+            fixed.source,
+            "{\n  \"b\": [{{#items}}{{&html}}{{/items}}, 1],\n  a: 3\n}"
+        );
+        assert_eq!(fixed.fixed, 5);
+        assert!(fixed
+            .diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.fix.is_empty()));
+    }
+
+    /// Replaces non-empty arrays with `[]`, or deletes their closing bracket
+    struct EmptyArrays {
+        broken: bool,
+    }
+
+    impl Rule for EmptyArrays {
+        fn id(&self) -> &'static str {
+            "empty-arrays"
+        }
+
+        fn check(&self, root: Node, reporter: &mut Reporter) {
+            for node in mustache_json5_ast::descendants(root) {
+                if node.kind() == "array" && node.named_child_count() > 0 {
+                    let fix = if self.broken {
+                        let end = node.end_byte();
+                        vec![Edit::delete(end - 1..end)]
+                    } else {
+                        vec![Edit::replace(node, "[]")]
+                    };
+                    reporter.report_with_fix(node, "Array is not empty", fix);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fix_defers_overlapping_edits() {
+        let mut linter = Linter::empty();
+        linter.set_rule(Box::new(EmptyArrays { broken: false }));
+        let fixed = linter.fix("[[1], [2]]").unwrap();
+
+        // The inner arrays are gone once the outer one is replaced
+        assert_eq!(fixed.source, "[]");
+        assert_eq!(fixed.fixed, 1);
+        assert!(fixed.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_fix_keeps_the_template_parsing() {
+        let mut linter = Linter::empty();
+        linter.set_rule(Box::new(EmptyArrays { broken: true }));
+        let fixed = linter.fix("[1]").unwrap();
+
+        assert_eq!(fixed.source, "[1]");
+        assert_eq!(fixed.fixed, 0);
+        assert_eq!(fixed.diagnostics.len(), 1);
+    }
+
+    fn lint_config(rules: serde_json::Value) -> LintConfig {
+        serde_json::from_value(serde_json::json!({ "rules": rules })).unwrap()
+    }
+
+    #[test]
+    fn test_configure_levels() {
+        let mut linter = Linter::new().unwrap();
+        linter
+            .configure(&lint_config(serde_json::json!({
+                "unsafe-unescaped": "off",
+                "duplicate-key": "error",
+                "deep-nesting": {"severity": "warn", "max_depth": 1}
+            })))
+            .unwrap();
+        let diagnostics = linter
+            .lint(r#"{"a": {{&body}}, "a": 1, "b": [{{#x}}{{#y}}1{{/y}}{{/x}}]}"#)
+            .unwrap();
+
+        assert_eq!(rules(&diagnostics), ["duplicate-key", "deep-nesting"]);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_configure_rejects_unknown_rules_and_options() {
+        let mut linter = Linter::new().unwrap();
+        let error = linter
+            .configure(&lint_config(serde_json::json!({"no-such-rule": "off"})))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Unknown lint rule `no-such-rule`");
+
+        let error = linter
+            .configure(&lint_config(serde_json::json!({
+                "duplicate-key": {"max_depth": 2}
+            })))
+            .unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "Invalid options for lint rule `duplicate-key`: \
+             Unknown option `max_depth` for lint rule `duplicate-key`"
+        );
+
+        let error = linter
+            .configure(&lint_config(serde_json::json!({
+                "trailing-comma": {"paths": []}
+            })))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown option `paths` for lint rule `trailing-comma`"
+        );
+    }
+}
@@ -0,0 +1,951 @@
+//! Lint rules implemented in Rust, for checks a query cannot express
+
+use anyhow::{bail, Context, Result};
+use mustache_json5_ast::{
+    descendants, AstNode, Member, MustacheInvertedSection, MustacheSection, MustacheUnescaped,
+    NameContent, Object, TemplateContent,
+};
+use tree_sitter::Node;
+
+use super::{Edit, Reporter, Rule, Severity};
+use mustache_json5_render::template::HELPERS;
+
+/// The rules every [`Linter`](super::Linter) starts with
+pub fn default_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(MismatchedSection),
+        Box::new(UnusedParam),
+        Box::new(ShadowedParam),
+        Box::new(DuplicateKey),
+        Box::new(UnreachableSection),
+        Box::new(DeepNesting::default()),
+        Box::new(UnescapedSyntax),
+        Box::new(UnsafeUnescaped::default()),
+    ]
+}
+
+/// `{{#a}}...{{/b}}`; the fix renames the closing tag
+pub struct MismatchedSection;
+
+impl Rule for MismatchedSection {
+    fn id(&self) -> &'static str {
+        "mismatched-section"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, root: Node, reporter: &mut Reporter) {
+        for section in descendants(root).into_iter().filter_map(Section::cast) {
+            let (Some(name), Some(end)) = (section.name, section.end) else {
+                continue;
+            };
+            let (open, close) = (reporter.text(name), reporter.text(end));
+            if open != close {
+                let message = format!(
+                    "`{{{{/{}}}}}` closes `{{{{{}{}}}}}`",
+                    close,
+                    if section.inverted { '^' } else { '#' },
+                    open
+                );
+                let fix = vec![Edit::replace(end, open)];
+                reporter.report_with_fix(end, message, fix);
+            }
+        }
+    }
+}
+
+/// `as |x|` parameters the section body never refers to
+///
+/// Parameters are positional, so one is only reported if no parameter after
+/// it is used either: in `as |item index|`, `item` is needed to name `index`.
+pub struct UnusedParam;
+
+impl Rule for UnusedParam {
+    fn id(&self) -> &'static str {
+        "unused-param"
+    }
+
+    fn check(&self, root: Node, reporter: &mut Reporter) {
+        for section in descendants(root).into_iter().filter_map(Section::cast) {
+            let mut later_used = false;
+            for parameter in section.parameters.iter().rev() {
+                let name = reporter.text(*parameter).to_string();
+                let used = section
+                    .body
+                    .iter()
+                    .any(|node| uses(*node, &name, reporter.source()));
+                if !used && !later_used {
+                    reporter.report(*parameter, format!("Parameter `{}` is never used", name));
+                }
+                later_used |= used;
+            }
+        }
+    }
+}
+
+/// `as |x|` parameters that hide a parameter of an enclosing section
+pub struct ShadowedParam;
+
+impl Rule for ShadowedParam {
+    fn id(&self) -> &'static str {
+        "shadowed-param"
+    }
+
+    fn check(&self, root: Node, reporter: &mut Reporter) {
+        shadowed(root, &mut Vec::new(), reporter);
+    }
+}
+
+fn shadowed<'tree>(node: Node<'tree>, scope: &mut Vec<String>, reporter: &mut Reporter) {
+    let Some(section) = Section::cast(node) else {
+        for child in named_children(node) {
+            shadowed(child, scope, reporter);
+        }
+        return;
+    };
+
+    let declared = scope.len();
+    for parameter in &section.parameters {
+        let name = reporter.text(*parameter).to_string();
+        if scope.contains(&name) {
+            reporter.report(
+                *parameter,
+                format!(
+                    "Parameter `{}` shadows a parameter of an enclosing section",
+                    name
+                ),
+            );
+        }
+        scope.push(name);
+    }
+    for child in &section.body {
+        shadowed(*child, scope, reporter);
+    }
+    scope.truncate(declared);
+}
+
+/// Object keys defined more than once; JSON5 silently keeps the last value,
+/// so the fix removes the earlier member
+///
+/// Keys under sections are compared too. Two keys in branches that cannot
+/// both render, like `{{#x}}` and `{{^x}}`, do not conflict, and keys only
+/// known when rendering, like `{{key}}: 1`, are never reported.
+pub struct DuplicateKey;
+
+impl Rule for DuplicateKey {
+    fn id(&self) -> &'static str {
+        "duplicate-key"
+    }
+
+    fn check(&self, root: Node, reporter: &mut Reporter) {
+        for object in descendants(root).into_iter().filter_map(Object::cast) {
+            let mut keys = Vec::new();
+            for child in object_children(object.node()) {
+                object_keys(
+                    child,
+                    false,
+                    &mut Vec::new(),
+                    0,
+                    reporter.source(),
+                    &mut keys,
+                );
+            }
+            for (index, key) in keys.iter().enumerate() {
+                let Some(earlier) = keys[..index]
+                    .iter()
+                    .rev()
+                    .find(|earlier| earlier.key == key.key && !exclusive(earlier, key))
+                else {
+                    continue;
+                };
+                let position = earlier.name.start_position();
+                let message = format!(
+                    "Key `{}` is already defined at line {}, column {}",
+                    key.key,
+                    position.row + 1,
+                    position.column + 1
+                );
+                // Removing a conditional member would change what renders
+                let fix = match (earlier.member, key.member) {
+                    (Some(member), Some(_)) => remove_member(member, reporter.source())
+                        .into_iter()
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                reporter.report_with_fix(key.name, message, fix);
+            }
+        }
+    }
+}
+
+/// A key written in an object, and the sections it renders under
+struct ObjectKey<'tree> {
+    key: String,
+    name: Node<'tree>,
+    /// The member, unless it is under a section
+    member: Option<Node<'tree>>,
+    branches: Vec<Branch>,
+}
+
+/// A section condition a key renders under
+#[derive(Clone)]
+struct Branch {
+    /// The context the condition is looked up in, as the start of the
+    /// section that pushed it (0 for the object's own context)
+    context: usize,
+    path: String,
+    truthy: bool,
+}
+
+/// Whether `a` and `b` are under sections that cannot both render
+fn exclusive(a: &ObjectKey, b: &ObjectKey) -> bool {
+    a.branches.iter().any(|a| {
+        b.branches
+            .iter()
+            .any(|b| a.context == b.context && a.path == b.path && a.truthy != b.truthy)
+    })
+}
+
+/// The named children of `object`, plus the sections error recovery moved
+/// out of it
+///
+/// With no comma between them, as in `{ {{#x}}"a": 1{{/x}}{{^x}}"a": 2{{/x}} }`,
+/// the object is closed with a missing `}` after the first section and the
+/// others follow it as siblings.
+fn object_children(object: Node) -> Vec<Node> {
+    let mut children = named_children(object);
+    let closed = object
+        .child(object.child_count().saturating_sub(1))
+        .is_some_and(|last| last.kind() == "}" && !last.is_missing());
+    if !closed {
+        let mut sibling = object.next_named_sibling();
+        while let Some(section) = sibling.filter(|node| Section::cast(*node).is_some()) {
+            children.push(section);
+            sibling = section.next_named_sibling();
+        }
+    }
+    children
+}
+
+/// Collect the keys in `node`, a child of an object
+///
+/// The grammar has no members inside sections, so `{{#x}}"a": 1{{/x}}` in
+/// an object parses with the `:` as an error. A name directly followed by
+/// that error in a section body is taken to be a key.
+fn object_keys<'tree>(
+    node: Node<'tree>,
+    in_section: bool,
+    branches: &mut Vec<Branch>,
+    context: usize,
+    source: &str,
+    keys: &mut Vec<ObjectKey<'tree>>,
+) {
+    if let Some(member) = Member::cast(node) {
+        let name = member.name().and_then(|name| name.child());
+        if let Some(key) = name.and_then(|name| key_text(name, source)) {
+            keys.push(ObjectKey {
+                key,
+                name: member.name().map_or(node, |name| name.node()),
+                member: (!in_section).then_some(node),
+                branches: branches.clone(),
+            });
+        }
+        return;
+    }
+
+    if let Some(section) = Section::cast(node) {
+        let (path, truthy) = section.condition(source);
+        let outer = branches.len();
+        if let Some(truthy) = truthy {
+            branches.push(Branch {
+                context,
+                path,
+                truthy,
+            });
+        }
+        let context = match section.keeps_context(source) {
+            true => context,
+            false => node.start_byte(),
+        };
+        // The body as parsed, including the errors around keys
+        let body = named_children(node)
+            .into_iter()
+            .filter(|child| *child != section.begin && !child.kind().ends_with("_end"));
+        for child in body {
+            object_keys(child, true, branches, context, source, keys);
+        }
+        branches.truncate(outer);
+        return;
+    }
+
+    // Keys inside nested objects belong to those objects
+    if !in_section || matches!(node.kind(), "object" | "array") {
+        return;
+    }
+    let followed_by_colon = node.next_sibling().is_some_and(|next| {
+        (next.is_error() || next.kind() == ":") && source[next.byte_range()].starts_with(':')
+    });
+    match NameContent::cast(node) {
+        Some(name) if followed_by_colon => {
+            if let Some(key) = key_text(name, source) {
+                keys.push(ObjectKey {
+                    key,
+                    name: node,
+                    member: None,
+                    branches: branches.clone(),
+                });
+            }
+        }
+        _ => {
+            for child in named_children(node) {
+                object_keys(child, in_section, branches, context, source, keys);
+            }
+        }
+    }
+}
+
+/// Remove `member`, its comma and the whitespace after it; `None` if a
+/// comment comes first, as it may describe the member
+fn remove_member(member: Node, source: &str) -> Option<Edit> {
+    let comma = member
+        .next_sibling()
+        .filter(|sibling| sibling.kind() == ",")?;
+    let rest = &source[comma.end_byte()..];
+    let end = comma.end_byte() + rest.len() - rest.trim_start().len();
+    Some(Edit::delete(member.start_byte()..end))
+}
+
+/// The key a member name stands for, or `None` if it is only known when
+/// rendering
+pub(crate) fn key_text(name: NameContent, source: &str) -> Option<String> {
+    let text = name.text(source);
+    match name {
+        NameContent::Identifier(_) => Some(text.to_string()),
+        NameContent::StringLiteral(_) if text.contains("{{") => None,
+        NameContent::StringLiteral(_) => {
+            let inner = &text[1..text.len() - 1];
+            if text.starts_with('"') {
+                serde_json::from_str(text).ok()
+            } else {
+                Some(inner.replace("\\'", "'"))
+            }
+        }
+        NameContent::MustacheInterpolation(_) | NameContent::MustacheUnescaped(_) => None,
+    }
+}
+
+/// Sections whose condition contradicts an enclosing one, such as
+/// `{{^x}}` inside `{{#if x}}`
+///
+/// Only `if`, `unless` and inverted sections are followed: they keep the
+/// context, so a name means the same inside them. Other sections push a new
+/// context, where the name may resolve to something else.
+pub struct UnreachableSection;
+
+impl Rule for UnreachableSection {
+    fn id(&self) -> &'static str {
+        "unreachable-section"
+    }
+
+    fn check(&self, root: Node, reporter: &mut Reporter) {
+        unreachable(root, &mut Vec::new(), reporter);
+    }
+}
+
+/// What is known inside a conditional section: `path` is truthy or falsy
+struct Condition<'tree> {
+    path: String,
+    truthy: bool,
+    tag: Node<'tree>,
+}
+
+fn unreachable<'tree>(
+    node: Node<'tree>,
+    known: &mut Vec<Condition<'tree>>,
+    reporter: &mut Reporter,
+) {
+    let Some(section) = Section::cast(node) else {
+        for child in named_children(node) {
+            unreachable(child, known, reporter);
+        }
+        return;
+    };
+
+    let (path, condition) = section.condition(reporter.source());
+
+    if let Some(truthy) = condition {
+        if let Some(contradiction) = known
+            .iter()
+            .find(|known| known.path == path && known.truthy != truthy)
+        {
+            let message = format!(
+                "`{}` never renders: `{}` is {} inside `{}`",
+                reporter.text(section.begin),
+                path,
+                if contradiction.truthy {
+                    "truthy"
+                } else {
+                    "falsy"
+                },
+                reporter.text(contradiction.tag)
+            );
+            reporter.report(section.begin, message);
+        }
+    }
+
+    // Only sections that keep the context carry what is known into the body
+    if !section.keeps_context(reporter.source()) {
+        for child in &section.body {
+            unreachable(*child, &mut Vec::new(), reporter);
+        }
+        return;
+    }
+
+    let outer = known.len();
+    if let Some(truthy) = condition {
+        known.push(Condition {
+            path,
+            truthy,
+            tag: section.begin,
+        });
+    }
+    for child in &section.body {
+        unreachable(*child, known, reporter);
+    }
+    known.truncate(outer);
+}
+
+/// `{{{x}}}`, written `{{&x}}` so unescaped output looks the same everywhere
+pub struct UnescapedSyntax;
+
+impl Rule for UnescapedSyntax {
+    fn id(&self) -> &'static str {
+        "unescaped-syntax"
+    }
+
+    fn check(&self, root: Node, reporter: &mut Reporter) {
+        for node in descendants(root) {
+            let Some(unescaped) = MustacheUnescaped::cast(node) else {
+                continue;
+            };
+            let Some(expression) = unescaped.child() else {
+                continue;
+            };
+            if reporter.text(node).starts_with("{{{") {
+                let replacement = format!("{{{{&{}}}}}", reporter.text(expression.node()));
+                let message = format!("Write `{}` as `{}`", reporter.text(node), replacement);
+                reporter.report_with_fix(node, message, vec![Edit::replace(node, replacement)]);
+            }
+        }
+    }
+}
+
+/// Unescaped output, whose data can break out of the JSON structure: a `"`
+/// ends the string it is inserted into, and in value or key position the data
+/// is written as raw JSON
+///
+/// Paths in `trusted`, and paths under them, are not reported. They are
+/// matched as written, so `item.html` inside `{{#each items as |item|}}` is
+/// trusted by `item` or `item.html`, not `items`.
+#[derive(Default)]
+pub struct UnsafeUnescaped {
+    pub trusted: Vec<String>,
+}
+
+impl Rule for UnsafeUnescaped {
+    fn id(&self) -> &'static str {
+        "unsafe-unescaped"
+    }
+
+    fn check(&self, root: Node, reporter: &mut Reporter) {
+        for node in descendants(root) {
+            match node.kind() {
+                "mustache_unescaped" => {
+                    let Some(path) = MustacheUnescaped::cast(node).and_then(|tag| tag.child())
+                    else {
+                        continue;
+                    };
+                    if self.is_trusted(reporter.text(path.node())) {
+                        continue;
+                    }
+                    let position = match node.parent().map(|parent| parent.kind()) {
+                        Some("name") => "a raw key",
+                        _ => "raw JSON",
+                    };
+                    let message = format!(
+                        "`{}` is output as {}, so its data can change the structure of the document",
+                        reporter.text(node),
+                        position
+                    );
+                    reporter.report(node, message);
+                }
+                "string" => {
+                    for (tag, path) in unescaped_tags(reporter.text(node)) {
+                        if !self.is_trusted(path) {
+                            let message = format!(
+                                "`{}` is inserted into a string without escaping, so a `\"` in its data ends the string",
+                                tag
+                            );
+                            reporter.report(node, message);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn configure(&mut self, options: &serde_json::Map<String, serde_json::Value>) -> Result<()> {
+        for (option, value) in options {
+            match option.as_str() {
+                "trusted" => {
+                    self.trusted = serde_json::from_value(value.clone())
+                        .context("`trusted` must be a list of variable paths")?
+                }
+                _ => bail!("Unknown option `{}` for lint rule `{}`", option, self.id()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl UnsafeUnescaped {
+    fn is_trusted(&self, path: &str) -> bool {
+        self.trusted.iter().any(|trusted| {
+            path.strip_prefix(trusted.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+    }
+}
+
+/// The `{{{x}}}` and `{{&x}}` tags in a string literal, with their paths
+fn unescaped_tags(text: &str) -> Vec<(&str, &str)> {
+    let mut tags = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let tag = &rest[start..];
+        let (close, path) = if let Some(path) = tag.strip_prefix("{{{") {
+            ("}}}", path)
+        } else if let Some(path) = tag.strip_prefix("{{&") {
+            ("}}", path)
+        } else {
+            rest = &tag[2..];
+            continue;
+        };
+        let Some(end) = path.find(close) else {
+            break;
+        };
+        let length = tag.len() - path.len() + end + close.len();
+        tags.push((&tag[..length], path[..end].trim()));
+        rest = &tag[length..];
+    }
+    tags
+}
+
+/// Sections nested more than `max_depth` deep
+pub struct DeepNesting {
+    pub max_depth: usize,
+}
+
+impl Default for DeepNesting {
+    fn default() -> Self {
+        DeepNesting { max_depth: 3 }
+    }
+}
+
+impl Rule for DeepNesting {
+    fn id(&self) -> &'static str {
+        "deep-nesting"
+    }
+
+    fn check(&self, root: Node, reporter: &mut Reporter) {
+        self.nesting(root, 0, reporter);
+    }
+
+    fn configure(&mut self, options: &serde_json::Map<String, serde_json::Value>) -> Result<()> {
+        for (option, value) in options {
+            match option.as_str() {
+                "max_depth" => {
+                    self.max_depth = value
+                        .as_u64()
+                        .filter(|depth| *depth > 0)
+                        .context("`max_depth` must be a positive integer")?
+                        as usize
+                }
+                _ => bail!("Unknown option `{}` for lint rule `{}`", option, self.id()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DeepNesting {
+    fn nesting(&self, node: Node, depth: usize, reporter: &mut Reporter) {
+        let depth = match Section::cast(node) {
+            Some(section) => {
+                if depth == self.max_depth {
+                    reporter.report(
+                        section.begin,
+                        format!(
+                            "Sections are nested more than {} levels deep",
+                            self.max_depth
+                        ),
+                    );
+                }
+                depth + 1
+            }
+            None => depth,
+        };
+        for child in named_children(node) {
+            self.nesting(child, depth, reporter);
+        }
+    }
+}
+
+/// A section or inverted section, with its parts
+struct Section<'tree> {
+    inverted: bool,
+    begin: Node<'tree>,
+    name: Option<Node<'tree>>,
+    expression: Option<Node<'tree>>,
+    parameters: Vec<Node<'tree>>,
+    body: Vec<Node<'tree>>,
+    end: Option<Node<'tree>>,
+}
+
+impl<'tree> Section<'tree> {
+    fn cast(node: Node<'tree>) -> Option<Self> {
+        let body =
+            |body: Vec<TemplateContent<'tree>>| body.iter().map(|child| child.node()).collect();
+        if let Some(section) = MustacheSection::cast(node) {
+            let begin = section.begin()?;
+            Some(Section {
+                inverted: false,
+                begin: begin.node(),
+                name: begin.tag_name().map(|name| name.node()),
+                expression: begin.expression().map(|expression| expression.node()),
+                parameters: begin.parameters().iter().map(|p| p.node()).collect(),
+                body: body(section.body()),
+                end: section
+                    .end()
+                    .and_then(|end| end.child())
+                    .map(|name| name.node()),
+            })
+        } else if let Some(section) = MustacheInvertedSection::cast(node) {
+            let begin = section.begin()?;
+            Some(Section {
+                inverted: true,
+                begin: begin.node(),
+                name: begin.tag_name().map(|name| name.node()),
+                expression: begin.expression().map(|expression| expression.node()),
+                parameters: begin.parameters().iter().map(|p| p.node()).collect(),
+                body: body(section.body()),
+                end: section
+                    .end()
+                    .and_then(|end| end.child())
+                    .map(|name| name.node()),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The block helper, for `{{#each items}}` and the like
+    fn helper(&self, source: &str) -> Option<String> {
+        let name = &source[self.name?.byte_range()];
+        (self.expression.is_some() && HELPERS.contains(&name)).then(|| name.to_string())
+    }
+
+    /// The path the section tests, and whether it renders when the path is
+    /// truthy or falsy; `None` for `each` and `with`, which do more than test
+    fn condition(&self, source: &str) -> (String, Option<bool>) {
+        let path = self
+            .expression
+            .or(self.name)
+            .map(|path| source[path.byte_range()].to_string())
+            .unwrap_or_default();
+        let truthy = match (self.inverted, self.helper(source).as_deref()) {
+            (true, _) | (false, Some("unless")) => Some(false),
+            (false, Some("if")) | (false, None) => Some(true),
+            _ => None,
+        };
+        (path, truthy)
+    }
+
+    /// Whether names mean the same in the body as outside: true for `if`,
+    /// `unless` and inverted sections, which do not push a context
+    fn keeps_context(&self, source: &str) -> bool {
+        self.inverted || matches!(self.helper(source).as_deref(), Some("if" | "unless"))
+    }
+}
+
+fn named_children(node: Node) -> Vec<Node> {
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor).collect()
+}
+
+/// Whether `node` may refer to `name`, as `{{name}}`, `{{name.field}}`,
+/// `{{#name}}` or inside a string; a section declaring its own `name` hides
+/// its body. Partials could refer to anything, so they count as uses.
+fn uses(node: Node, name: &str, source: &str) -> bool {
+    match node.kind() {
+        "mustache_partial" => return true,
+        "identifier_expression" => {
+            let first = node
+                .parent()
+                .filter(|parent| parent.kind() == "path_expression")
+                .and_then(|parent| parent.named_child(0));
+            if first.is_none_or(|first| first == node) {
+                return &source[node.byte_range()] == name;
+            }
+        }
+        "tag_name" => {
+            let tag = &source[node.byte_range()];
+            return tag.split('.').next() == Some(name);
+        }
+        "string" => return string_uses(&source[node.byte_range()], name),
+        _ => {}
+    }
+
+    if let Some(section) = Section::cast(node) {
+        let head = section.name.into_iter().chain(section.expression);
+        let redeclared = section
+            .parameters
+            .iter()
+            .any(|parameter| &source[parameter.byte_range()] == name);
+        let mut parts: Vec<Node> = head.collect();
+        if !redeclared {
+            parts.extend(section.body);
+        }
+        return parts.into_iter().any(|part| uses(part, name, source));
+    }
+
+    named_children(node)
+        .into_iter()
+        .any(|child| uses(child, name, source))
+}
+
+/// Whether a string literal's embedded tags may refer to `name`
+fn string_uses(text: &str, name: &str) -> bool {
+    text.split("{{").skip(1).any(|tag| {
+        let tag = tag.split("}}").next().unwrap_or("");
+        if tag.starts_with('!') {
+            return false;
+        }
+        if tag.starts_with('>') {
+            return true;
+        }
+        tag.split(|c: char| c.is_whitespace() || "{}&#^/|".contains(c))
+            .any(|word| word.split('.').next() == Some(name))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Linter;
+    use super::*;
+
+    fn check(rule: impl Rule + 'static, source: &str) -> Vec<String> {
+        let mut linter = Linter::empty();
+        linter.set_rule(Box::new(rule));
+        linter
+            .lint(source)
+            .unwrap()
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_mismatched_section() {
+        assert_eq!(
+            check(MismatchedSection, "[{{#a}}1{{/b}}, {{^c}}2{{/c}}]"),
+            ["`{{/b}}` closes `{{#a}}`"]
+        );
+    }
+
+    #[test]
+    fn test_unused_param() {
+        assert!(check(
+            UnusedParam,
+            r#"[{{#each items as |item|}}{"id": {{item.id}}}{{/each}}]"#
+        )
+        .is_empty());
+        assert!(check(
+            UnusedParam,
+            r#"[{{#each items as |item|}}"{{item}}"{{/each}}]"#
+        )
+        .is_empty());
+        assert_eq!(
+            check(UnusedParam, "[{{#each items as |item|}}{{name}}{{/each}}]"),
+            ["Parameter `item` is never used"]
+        );
+        // `item` must be named to get at `i`
+        assert!(check(UnusedParam, "[{{#each items as |item i|}}{{i}}{{/each}}]").is_empty());
+        // The inner `item` is a different variable
+        assert_eq!(
+            check(
+                UnusedParam,
+                "[{{#each a as |item|}}{{#each b as |item|}}{{item}}{{/each}}{{/each}}]"
+            ),
+            ["Parameter `item` is never used"]
+        );
+        assert!(check(UnusedParam, "[{{#each a as |item|}}{{> row}}{{/each}}]").is_empty());
+    }
+
+    #[test]
+    fn test_shadowed_param() {
+        assert_eq!(
+            check(
+                ShadowedParam,
+                "[{{#each a as |x|}}{{#each x.b as |x|}}{{x}}{{/each}}{{/each}}]"
+            ),
+            ["Parameter `x` shadows a parameter of an enclosing section"]
+        );
+        assert!(check(
+            ShadowedParam,
+            "[{{#each a as |x|}}{{x}}{{/each}}, {{#each b as |x|}}{{x}}{{/each}}]"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_key() {
+        assert_eq!(
+            check(
+                DuplicateKey,
+                r#"{"a": 1, b: 2, 'a': 3, "b": 4, {{k}}: 5, {{k}}: 6}"#
+            ),
+            [
+                "Key `a` is already defined at line 1, column 2",
+                "Key `b` is already defined at line 1, column 10"
+            ]
+        );
+        assert!(check(DuplicateKey, r#"{"a": {"a": 1}}"#).is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_key_in_sections() {
+        // Exclusive branches never render together
+        let exclusive = [
+            r#"{ {{#x}}"a": 1{{/x}}, {{^x}}"a": 2{{/x}}}"#,
+            r#"{ {{#if x}}"a": 1{{/if}}, {{#unless x}}"a": 2{{/unless}}}"#,
+            r#"{ {{#if x}}"a": 1{{/if}}, {{^x}}'a': 2, b: 3{{/x}}}"#,
+            r#"{ {{#y}}{{#x}}"a": 1{{/x}}{{^x}}"a": 2{{/x}}{{/y}}}"#,
+        ];
+        for source in exclusive {
+            assert!(check(DuplicateKey, source).is_empty(), "{}", source);
+        }
+
+        for source in [
+            r#"{"a": 0, {{#x}}"a": 1{{/x}}}"#,
+            r#"{"a": 0, {{#with x}}"a": 1{{/with}}}"#,
+            r#"{"a": 0, {{#each x}}"a": 1{{/each}}}"#,
+        ] {
+            assert_eq!(
+                check(DuplicateKey, source),
+                ["Key `a` is already defined at line 1, column 2"],
+                "{}",
+                source
+            );
+        }
+        assert_eq!(
+            check(
+                DuplicateKey,
+                r#"{ {{#x}}"a": 1{{/x}}, {{#y}}"b": 2, a: 3{{/y}}}"#
+            ),
+            ["Key `a` is already defined at line 1, column 9"]
+        );
+        // `{{#x}}` pushes `x`, so `{{^x}}` inside it tests something else
+        assert_eq!(
+            check(DuplicateKey, r#"{ {{#x}}"a": 1, {{^x}}"a": 2{{/x}}{{/x}}}"#).len(),
+            1
+        );
+        // Interpolated names are unknown until rendering
+        assert!(check(DuplicateKey, r#"{ {{#x}}{{k}}: 1{{/x}}, {{k}}: 2}"#).is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_key_in_adjacent_sections() {
+        assert!(check(DuplicateKey, r#"{ {{#x}}"a":1{{/x}}{{^x}}"a":2{{/x}} }"#).is_empty());
+        assert_eq!(
+            check(DuplicateKey, r#"{ {{#x}}"a":1{{/x}}{{#x}}"a":2{{/x}} }"#),
+            ["Key `a` is already defined at line 1, column 9"]
+        );
+        assert_eq!(
+            check(DuplicateKey, r#"{ {{#x}}"a":1{{/x}}{{#y}}"a":2{{/y}} }"#),
+            ["Key `a` is already defined at line 1, column 9"]
+        );
+        assert_eq!(
+            check(
+                DuplicateKey,
+                r#"{ {{#x}}"a":1{{/x}}{{^x}}"b":2{{/x}}{{#y}}"a":3{{/y}} }"#
+            ),
+            ["Key `a` is already defined at line 1, column 9"]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_section() {
+        assert_eq!(
+            check(UnreachableSection, "[{{#if x}}{{^x}}1{{/x}}{{/if}}]"),
+            ["`{{^x}}` never renders: `x` is truthy inside `{{#if x}}`"]
+        );
+        assert_eq!(
+            check(UnreachableSection, "[{{^x}}{{#if x}}1{{/if}}{{/x}}]"),
+            ["`{{#if x}}` never renders: `x` is falsy inside `{{^x}}`"]
+        );
+        // `{{#x}}` pushes `x` as the context, where `x` may be something else
+        assert!(check(UnreachableSection, "[{{#x}}{{^x}}1{{/x}}{{/x}}]").is_empty());
+        assert!(check(UnreachableSection, "[{{#if x}}{{^y}}1{{/y}}{{/if}}]").is_empty());
+    }
+
+    #[test]
+    fn test_unescaped_syntax() {
+        assert_eq!(
+            check(UnescapedSyntax, "[{{{body}}}, {{&html}}]"),
+            ["Write `{{{body}}}` as `{{&body}}`"]
+        );
+    }
+
+    #[test]
+    fn test_unsafe_unescaped() {
+        assert_eq!(
+            check(
+                UnsafeUnescaped::default(),
+                r#"{"a": {{&body}}, {{{key}}}: 1, "b": "<p>{{{html}}} {{name}}</p>", "c": {{safe}}}"#
+            ),
+            [
+                "`{{&body}}` is output as raw JSON, so its data can change the structure of the document",
+                "`{{{key}}}` is output as a raw key, so its data can change the structure of the document",
+                "`{{{html}}}` is inserted into a string without escaping, so a `\"` in its data ends the string",
+            ]
+        );
+
+        let rule = UnsafeUnescaped {
+            trusted: vec!["config".to_string(), "page.html".to_string()],
+        };
+        assert!(check(
+            rule,
+            r#"["{{&page.html}}", {{&config}}, {{{config.raw}}}, {{{ page.html }}}]"#
+        )
+        .is_empty());
+
+        let rule = UnsafeUnescaped {
+            trusted: vec!["page".to_string()],
+        };
+        assert_eq!(check(rule, "[{{&pages}}]").len(), 1);
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let source = "[{{#a}}{{#b}}{{^c}}{{#d}}1{{/d}}{{/c}}{{/b}}{{/a}}]";
+        assert_eq!(
+            check(DeepNesting::default(), source),
+            ["Sections are nested more than 3 levels deep"]
+        );
+        assert!(check(DeepNesting { max_depth: 4 }, source).is_empty());
+    }
+}
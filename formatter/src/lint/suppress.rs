@@ -0,0 +1,134 @@
+//! Inline suppression comments
+//!
+//! Both JSON5 comments and mustache comments can silence diagnostics:
+//!
+//! ```text
+//! {{! mjson5-lint-disable-next-line duplicate-key }}
+//! // mjson5-lint-disable-next-line unused-param, deep-nesting -- generated
+//! /* mjson5-lint-disable */
+//! ```
+//!
+//! `disable-next-line` applies to the line after the comment ends, `disable`
+//! to the whole file. Rules are separated by spaces or commas; without any,
+//! every rule is silenced. Anything after ` -- ` is a reason and is ignored.
+
+use mustache_json5_ast::descendants;
+use tree_sitter::Node;
+
+use super::Diagnostic;
+
+const DISABLE_NEXT_LINE: &str = "mjson5-lint-disable-next-line";
+const DISABLE: &str = "mjson5-lint-disable";
+
+/// Rules silenced by a directive; empty means all of them
+type Rules = Vec<String>;
+
+/// The suppression directives found in a template
+#[derive(Debug, Default)]
+pub struct Suppressions {
+    file: Vec<Rules>,
+    /// Zero-based line a directive applies to
+    lines: Vec<(usize, Rules)>,
+}
+
+impl Suppressions {
+    /// Collect the directives in the comments under `root`
+    pub fn collect(root: Node, source: &str) -> Self {
+        let mut suppressions = Suppressions::default();
+        for node in descendants(root) {
+            let text = match node.kind() {
+                "mustache_comment" => node
+                    .child_by_field_name("content")
+                    .or_else(|| node.named_child(0))
+                    .map_or("", |content| &source[content.byte_range()]),
+                "comment" => {
+                    let text = &source[node.byte_range()];
+                    match text.strip_prefix("//") {
+                        Some(line) => line,
+                        None => text
+                            .strip_prefix("/*")
+                            .and_then(|text| text.strip_suffix("*/"))
+                            .unwrap_or(text),
+                    }
+                }
+                _ => continue,
+            };
+            let text = text.trim();
+            let text = text.split_once(" -- ").map_or(text, |(text, _)| text);
+            if let Some(rules) = directive(text, DISABLE_NEXT_LINE) {
+                let line = node.end_position().row + 1;
+                suppressions.lines.push((line, rules));
+            } else if let Some(rules) = directive(text, DISABLE) {
+                suppressions.file.push(rules);
+            }
+        }
+        suppressions
+    }
+
+    /// Whether `diagnostic` is silenced by a directive
+    pub fn suppresses(&self, diagnostic: &Diagnostic) -> bool {
+        let covers = |rules: &Rules| rules.is_empty() || rules.contains(&diagnostic.rule);
+        self.file.iter().any(covers)
+            || self
+                .lines
+                .iter()
+                .any(|(line, rules)| *line + 1 == diagnostic.line && covers(rules))
+    }
+}
+
+/// The rules named by `text` if it is the directive `name`
+fn directive(text: &str, name: &str) -> Option<Rules> {
+    let rest = text.strip_prefix(name)?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(
+        rest.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|rule| !rule.is_empty())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Linter;
+
+    fn rules(source: &str) -> Vec<(String, usize)> {
+        Linter::new()
+            .unwrap()
+            .lint(source)
+            .unwrap()
+            .into_iter()
+            .map(|diagnostic| (diagnostic.rule, diagnostic.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_disable_next_line() {
+        let source = "{\n  \"a\": 1,\n  {{! mjson5-lint-disable-next-line duplicate-key }},\n  \"a\": 2,\n  \"b\": 1,\n  \"b\": 2\n}";
+        assert_eq!(rules(source), vec![("duplicate-key".to_string(), 6)]);
+    }
+
+    #[test]
+    fn test_json_comments() {
+        let source = "{\n  \"a\": 1,\n  // mjson5-lint-disable-next-line unused-param, duplicate-key -- intended\n  \"a\": 2\n}";
+        assert!(rules(source).is_empty());
+
+        let source = "/* mjson5-lint-disable */\n{\"a\": 1, \"a\": 2}";
+        assert!(rules(source).is_empty());
+    }
+
+    #[test]
+    fn test_other_rules_still_reported() {
+        let source =
+            "{\n  \"a\": 1,\n  {{! mjson5-lint-disable-next-line deep-nesting }},\n  \"a\": 2\n}";
+        assert_eq!(rules(source), vec![("duplicate-key".to_string(), 4)]);
+    }
+
+    #[test]
+    fn test_not_a_directive() {
+        let source = "{\n  \"a\": 1,\n  {{! mjson5-lint-disabled }},\n  \"a\": 2\n}";
+        assert_eq!(rules(source).len(), 1);
+    }
+}
@@ -1,86 +1,514 @@
 use anyhow::{Context, Result};
-use clap::{Arg, ArgAction, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use colored::*;
+use std::ffi::OsString;
 use std::fs;
 use std::io::{self, Read};
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use walkdir::WalkDir;
 
-mod config;
-mod formatter;
-
-use config::Config;
-use formatter::MustacheJson5Formatter;
+use mustache_json5_fmt::cache::{FormatCache, DEFAULT_CACHE_FILE};
+use mustache_json5_fmt::git::{self, DiffTarget};
+use mustache_json5_fmt::hook::{self, HookOptions};
+use mustache_json5_fmt::infer::StyleStats;
+use mustache_json5_fmt::inspect::{self, TreeFormat};
+use mustache_json5_fmt::lint::{Linter, Severity};
+use mustache_json5_fmt::watch::{self, WatchEvent, WatchOptions};
+use mustache_json5_fmt::{
+    is_template_file, Config, MustacheJson5Formatter, RuleConfig, TrailingCommaStyle,
+};
+use mustache_json5_render::{
+    AnalyzeOptions, EscapeMode, FileSystemResolver, MustacheJson5Renderer, ValidateOptions,
+};
 
 fn main() -> Result<()> {
-    let matches = Command::new("mustache-json5-fmt")
+    let matches = cli().get_matches_from(subcommand_first(std::env::args_os().collect()));
+
+    match matches.subcommand() {
+        Some(("fmt", sub_matches)) => {
+            let mode = Mode::Format {
+                write: sub_matches.get_flag("write"),
+            };
+            run_format(sub_matches, mode, load_config(sub_matches)?)
+        }
+        Some(("check", sub_matches)) => {
+            run_format(sub_matches, Mode::Check, load_config(sub_matches)?)
+        }
+        Some(("parse", sub_matches)) => handle_parse(sub_matches),
+        Some(("render", sub_matches)) => handle_render(sub_matches),
+        Some(("analyze", sub_matches)) => handle_analyze(sub_matches),
+        Some(("schema", sub_matches)) => handle_schema(sub_matches),
+        Some(("lint", sub_matches)) => handle_lint(sub_matches),
+        Some(("init-config", sub_matches)) => handle_init_config(sub_matches),
+        Some(("hook", sub_matches)) => handle_hook(sub_matches),
+        _ => {
+            // Flag form kept for existing integrations such as the Zed extension
+            let mode = if matches.get_flag("check") {
+                Mode::Check
+            } else {
+                Mode::Format {
+                    write: matches.get_flag("write"),
+                }
+            };
+            run_format(&matches, mode, load_flag_form_config(&matches)?)
+        }
+    }
+}
+
+/// Move the subcommand in front of any options given before it
+///
+/// The command line is the flag form unless its first positional argument
+/// names a subcommand. Options before the subcommand, like `--verbose` in
+/// `--verbose fmt a.mjson5`, then apply to the subcommand as if they came
+/// after it; `--` ends the options, so `-- check` is always a file.
+fn subcommand_first(mut args: Vec<OsString>) -> Vec<OsString> {
+    let command = cli();
+    let takes_value = |arg: &Arg| arg.get_action().takes_values();
+    let mut index = 1;
+    while let Some(arg) = args.get(index).and_then(|arg| arg.to_str()) {
+        if arg == "--" {
+            return args;
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            let needs_value = !long.contains('=')
+                && command
+                    .get_arguments()
+                    .any(|option| option.get_long() == Some(long) && takes_value(option));
+            index += if needs_value { 2 } else { 1 };
+        } else if let Some(shorts) = arg.strip_prefix('-').filter(|shorts| !shorts.is_empty()) {
+            // The first short option that takes a value ends a cluster such
+            // as `-vc`, and uses the next argument unless it has one attached
+            let needs_value = shorts.char_indices().find_map(|(i, short)| {
+                command
+                    .get_arguments()
+                    .any(|option| option.get_short() == Some(short) && takes_value(option))
+                    .then_some(i + short.len_utf8() == shorts.len())
+            });
+            index += if needs_value == Some(true) { 2 } else { 1 };
+        } else {
+            let is_subcommand = arg == "help"
+                || command
+                    .get_subcommands()
+                    .any(|subcommand| subcommand.get_name() == arg);
+            if is_subcommand {
+                let subcommand = args.remove(index);
+                args.insert(1, subcommand);
+            }
+            return args;
+        }
+    }
+    args
+}
+
+/// What to do with each formatted file
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    /// Print formatted output, or write it back to the file
+    Format { write: bool },
+    /// Report files that are not formatted and exit with code 1
+    Check,
+}
+
+fn cli() -> Command {
+    Command::new("mustache-json5-fmt")
         .version(env!("CARGO_PKG_VERSION"))
         .about("A formatter for Mustache JSON5 templates")
-        .arg(
-            Arg::new("files")
-                .help("Files to format")
-                .num_args(1..)
-                .value_name("FILE"),
-        )
+        .args_conflicts_with_subcommands(true)
+        .args(global_args())
+        .args(file_args())
         .arg(
             Arg::new("check")
                 .long("check")
-                .help("Check if files are formatted (exit code 1 if not)")
+                .help("Check if files are formatted (exit code 1 if not); same as `check`")
                 .action(ArgAction::SetTrue),
         )
-        .arg(
-            Arg::new("write")
-                .long("write")
-                .short('w')
-                .help("Write formatted output back to files")
-                .action(ArgAction::SetTrue),
+        .arg(write_arg())
+        .subcommand(
+            Command::new("fmt")
+                .about("Format templates, printing the result or writing it back")
+                .args(file_args())
+                .arg(write_arg()),
         )
-        .arg(
-            Arg::new("stdin")
-                .long("stdin")
-                .help("Read from stdin")
-                .action(ArgAction::SetTrue),
+        .subcommand(
+            Command::new("check")
+                .about("Check if templates are formatted (exit code 1 if not)")
+                .args(file_args()),
         )
-        .arg(
-            Arg::new("indent-size")
-                .long("indent-size")
-                .help("Number of spaces for indentation")
-                .value_name("SIZE")
-                .default_value("2"),
+        .subcommand(
+            Command::new("parse")
+                .about("Print the syntax tree of a template")
+                .arg(
+                    Arg::new("file")
+                        .help("Template to parse, or - for stdin")
+                        .value_name("FILE")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .help("Output format for the tree")
+                        .value_parser(["sexp", "outline", "json"])
+                        .default_value("sexp"),
+                )
+                .arg(
+                    Arg::new("query")
+                        .long("query")
+                        .short('q')
+                        .help("Print the captures of a query file instead of the tree")
+                        .value_name("SCM"),
+                ),
         )
-        .arg(
-            Arg::new("tab-width")
-                .long("tab-width")
-                .help("Width of tab characters")
-                .value_name("WIDTH")
-                .default_value("2"),
+        .subcommand(
+            Command::new("render")
+                .about("Render a template with JSON data")
+                .arg(
+                    Arg::new("file")
+                        .help("Template to render, or - for stdin")
+                        .value_name("FILE")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("data")
+                        .long("data")
+                        .short('d')
+                        .help("JSON file with the context to render [default: {}]")
+                        .value_name("DATA"),
+                )
+                .args(partial_args())
+                .arg(
+                    Arg::new("escape")
+                        .long("escape")
+                        .help("How {{name}} values are escaped")
+                        .value_parser(["json", "html", "none"])
+                        .default_value("json"),
+                )
+                .arg(
+                    Arg::new("validate")
+                        .long("validate")
+                        .help("Fail if the output is not valid JSON5, pointing at the template node responsible")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("strict-json")
+                        .long("strict-json")
+                        .help("Like --validate, but require plain JSON")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Write the result to a file instead of stdout")
+                        .value_name("PATH"),
+                ),
         )
-        .arg(
-            Arg::new("use-tabs")
-                .long("use-tabs")
-                .help("Use tabs for indentation")
-                .action(ArgAction::SetTrue),
+        .subcommand(
+            Command::new("analyze")
+                .about("Check that a template renders valid JSON5 for every combination of sections")
+                .arg(
+                    Arg::new("file")
+                        .help("Template to analyze, or - for stdin")
+                        .value_name("FILE")
+                        .required(true),
+                )
+                .args(partial_args())
+                .arg(
+                    Arg::new("strict-json")
+                        .long("strict-json")
+                        .help("Require plain JSON rather than JSON5")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("max-combinations")
+                        .long("max-combinations")
+                        .help("Upper bound on section combinations to check")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("4096"),
+                ),
         )
-        .arg(
-            Arg::new("config")
-                .long("config")
-                .short('c')
-                .help("Path to configuration file")
-                .value_name("PATH"),
+        .subcommand(
+            Command::new("schema")
+                .about("Check templates against a JSON Schema for their data")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("check")
+                        .about("Report variables and sections the schema does not define")
+                        .arg(
+                            Arg::new("file")
+                                .help("Template to check, or - for stdin")
+                                .value_name("FILE")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("schema")
+                                .long("schema")
+                                .short('s')
+                                .help("JSON Schema describing the render context")
+                                .value_name("PATH")
+                                .required(true),
+                        )
+                        .args(partial_args()),
+                )
+                .subcommand(
+                    Command::new("infer")
+                        .about("Describe the data a template expects")
+                        .arg(
+                            Arg::new("file")
+                                .help("Template to read, or - for stdin")
+                                .value_name("FILE")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .short('f')
+                                .help("Output format")
+                                .value_parser(["json-schema", "typescript", "rust"])
+                                .default_value("json-schema"),
+                        )
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .help("Name of the generated TypeScript or Rust type")
+                                .value_name("NAME")
+                                .default_value("Context"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .long("output")
+                                .short('o')
+                                .help("Write the result to a file instead of stdout")
+                                .value_name("PATH"),
+                        )
+                        .args(partial_args()),
+                ),
         )
-        .arg(
-            Arg::new("verbose")
-                .long("verbose")
-                .short('v')
-                .help("Verbose output")
-                .action(ArgAction::SetTrue),
+        .subcommand(
+            Command::new("lint")
+                .about("Report problems found by the lint rules (exit code 1 on errors)")
+                .arg(
+                    Arg::new("files")
+                        .help("Templates to lint, or - for stdin [default: templates under the current directory]")
+                        .num_args(1..)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("query")
+                        .long("query")
+                        .short('q')
+                        .help("Lint query to run instead of the bundled queries/lints.scm")
+                        .value_name("SCM"),
+                )
+                .arg(
+                    Arg::new("fix")
+                        .long("fix")
+                        .help("Apply fixes for problems that have one, writing back to the files")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("max-depth")
+                        .long("max-depth")
+                        .help("Deepest section nesting allowed by the deep-nesting rule [default: 3]")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u64).range(1..)),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .help("Output format for diagnostics")
+                        .value_parser(["text", "json"])
+                        .default_value("text"),
+                ),
         )
-        .get_matches();
+        .subcommand(
+            Command::new("init-config")
+                .about("Write a configuration file matching the style of existing templates")
+                .arg(
+                    Arg::new("paths")
+                        .help("Templates or directories to scan [default: .]")
+                        .num_args(1..)
+                        .value_name("PATH"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Where to write the configuration")
+                        .value_name("PATH")
+                        .default_value(".mustache-json5-fmt.json"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .help("Overwrite an existing configuration file")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("stdout")
+                        .long("stdout")
+                        .help("Print the configuration instead of writing it")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["output", "force"]),
+                ),
+        )
+        .subcommand(
+            Command::new("hook")
+                .about("Manage the git pre-commit hook")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("install")
+                        .about("Install a pre-commit hook that checks staged templates")
+                        .arg(
+                            Arg::new("fix")
+                                .long("fix")
+                                .help(
+                                    "Format staged templates in the index instead of failing",
+                                )
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("force")
+                                .long("force")
+                                .help("Replace an existing pre-commit hook")
+                                .action(ArgAction::SetTrue),
+                        ),
+                ),
+        )
+}
+
+/// Options for locating partials, shared by `render`, `analyze` and `schema`
+fn partial_args() -> Vec<Arg> {
+    vec![
+        Arg::new("partial")
+            .long("partial")
+            .short('p')
+            .help("Partial available as {{> NAME}}, read from PATH")
+            .value_name("NAME=PATH")
+            .action(ArgAction::Append),
+        Arg::new("partials-dir")
+            .long("partials-dir")
+            .short('I')
+            .help("Directory to search for partials, with dotted names as subdirectories [default: the template's directory]")
+            .value_name("DIR")
+            .action(ArgAction::Append),
+    ]
+}
+
+/// Options shared by every subcommand
+fn global_args() -> Vec<Arg> {
+    vec![
+        Arg::new("indent-size")
+            .long("indent-size")
+            .help("Number of spaces for indentation [default: 2]")
+            .value_name("SIZE")
+            .global(true),
+        Arg::new("tab-width")
+            .long("tab-width")
+            .help("Width of tab characters [default: 2]")
+            .value_name("WIDTH")
+            .global(true),
+        Arg::new("use-tabs")
+            .long("use-tabs")
+            .help("Use tabs for indentation")
+            .action(ArgAction::SetTrue)
+            .global(true),
+        Arg::new("config")
+            .long("config")
+            .short('c')
+            .help("Path to configuration file")
+            .value_name("PATH")
+            .global(true),
+        Arg::new("no-cache")
+            .long("no-cache")
+            .help("Do not read or write the formatting cache")
+            .action(ArgAction::SetTrue)
+            .global(true),
+        Arg::new("cache-location")
+            .long("cache-location")
+            .help("Path to the formatting cache file")
+            .value_name("PATH")
+            .default_value(DEFAULT_CACHE_FILE)
+            .conflicts_with("no-cache")
+            .global(true),
+        Arg::new("verbose")
+            .long("verbose")
+            .short('v')
+            .help("Verbose output")
+            .action(ArgAction::SetTrue)
+            .global(true),
+    ]
+}
+
+/// Options selecting which templates `fmt` and `check` operate on
+fn file_args() -> Vec<Arg> {
+    vec![
+        Arg::new("files")
+            .help("Files to format")
+            .num_args(1..)
+            .value_name("FILE"),
+        Arg::new("stdin")
+            .long("stdin")
+            .help("Read from stdin")
+            .action(ArgAction::SetTrue),
+        Arg::new("watch")
+            .long("watch")
+            .help("Watch a directory and format templates as they are saved")
+            .value_name("DIR")
+            .conflicts_with_all(["stdin", "files"]),
+        Arg::new("debounce")
+            .long("debounce")
+            .help("Milliseconds to wait for a burst of changes to settle in watch mode")
+            .value_name("MS")
+            .default_value("200")
+            .requires("watch"),
+        Arg::new("changed-since")
+            .long("changed-since")
+            .help("Only process templates changed relative to a git revision")
+            .value_name("REV")
+            .conflicts_with_all(["stdin", "watch", "files"]),
+        Arg::new("staged")
+            .long("staged")
+            .help("Only process templates with changes staged in git")
+            .action(ArgAction::SetTrue)
+            .conflicts_with_all(["stdin", "watch", "files", "changed-since"]),
+        Arg::new("lines-changed-only")
+            .long("lines-changed-only")
+            .help("Only format lines changed according to git")
+            .action(ArgAction::SetTrue),
+    ]
+}
 
-    // Load configuration
-    let mut config = Config::default();
+fn write_arg() -> Arg {
+    Arg::new("write")
+        .long("write")
+        .short('w')
+        .help("Write formatted output back to files")
+        .action(ArgAction::SetTrue)
+}
 
-    // Override with command line arguments
+/// Load the configuration file, then apply options given on the command line
+fn load_config(matches: &ArgMatches) -> Result<Config> {
+    let config_path = matches.get_one::<String>("config").map(String::as_str);
+    let config = Config::load_with_fallback(config_path)?;
+    apply_config_args(config, matches)
+}
+
+/// The flag form keeps its original resolution: the defaults, or the file
+/// given with `--config`, without searching for one
+fn load_flag_form_config(matches: &ArgMatches) -> Result<Config> {
+    let config = match matches.get_one::<String>("config") {
+        Some(path) => Config::from_file(path)?,
+        None => Config::default(),
+    };
+    apply_config_args(config, matches)
+}
+
+fn apply_config_args(mut config: Config, matches: &ArgMatches) -> Result<Config> {
     if let Some(indent_size) = matches.get_one::<String>("indent-size") {
         config.indent_size = indent_size.parse().context("Invalid indent size")?;
     }
@@ -93,9 +521,52 @@ fn main() -> Result<()> {
         config.use_tabs = true;
     }
 
+    Ok(config)
+}
+
+fn run_format(matches: &ArgMatches, mode: Mode, config: Config) -> Result<()> {
     let verbose = matches.get_flag("verbose");
-    let check_mode = matches.get_flag("check");
-    let write_mode = matches.get_flag("write");
+    let check_mode = matches!(mode, Mode::Check);
+    let write_mode = matches!(mode, Mode::Format { write: true });
+
+    if let Some(dir) = matches.get_one::<String>("watch") {
+        let debounce = matches
+            .get_one::<String>("debounce")
+            .map(|ms| ms.parse())
+            .transpose()
+            .context("Invalid debounce")?
+            .unwrap_or(200);
+        let options = WatchOptions {
+            check: check_mode,
+            debounce: Duration::from_millis(debounce),
+        };
+        return handle_watch(Path::new(dir), config, &options, verbose);
+    }
+
+    let diff_target = if let Some(revision) = matches.get_one::<String>("changed-since") {
+        Some(DiffTarget::Revision(revision.clone()))
+    } else if matches.get_flag("staged") {
+        Some(DiffTarget::Staged)
+    } else {
+        None
+    };
+
+    let lines_changed_only = matches.get_flag("lines-changed-only");
+    if lines_changed_only && diff_target.is_none() {
+        anyhow::bail!("--lines-changed-only requires --changed-since or --staged");
+    }
+
+    // Partial formatting says nothing about the rest of the file, so skip the cache
+    let mut cache =
+        if matches.get_flag("no-cache") || matches.get_flag("stdin") || lines_changed_only {
+            None
+        } else {
+            let cache_location = matches
+                .get_one::<String>("cache-location")
+                .map(String::as_str)
+                .unwrap_or(DEFAULT_CACHE_FILE);
+            Some(FormatCache::load(cache_location, &config)?)
+        };
 
     // Initialize formatter
     let mut formatter = MustacheJson5Formatter::new(config)?;
@@ -105,12 +576,36 @@ fn main() -> Result<()> {
         return handle_stdin(&mut formatter, check_mode);
     }
 
-    // Get files to process
-    let files: Vec<String> = if let Some(file_args) = matches.get_many::<String>("files") {
-        file_args.cloned().collect()
+    // Get files to process, with the line ranges to restrict formatting to
+    let files: Vec<(String, Option<Vec<Range<usize>>>)> = if let Some(target) = &diff_target {
+        let current_dir = std::env::current_dir()
+            .and_then(fs::canonicalize)
+            .context("Failed to get current directory")?;
+        git::changed_files(&current_dir, target)?
+            .into_iter()
+            .map(|file| {
+                let path = file.path.strip_prefix(&current_dir).unwrap_or(&file.path);
+                let lines = lines_changed_only.then_some(file.lines);
+                (path.to_string_lossy().into_owned(), lines)
+            })
+            .collect()
     } else {
-        // If no files specified, look for mustache_json5 files in current directory
-        find_mustache_json5_files(".")?
+        let patterns: Vec<String> = if let Some(file_args) = matches.get_many::<String>("files") {
+            file_args.cloned().collect()
+        } else {
+            // If no files specified, look for mustache_json5 files in current directory
+            find_mustache_json5_files(".")?
+        };
+
+        let mut files = Vec::new();
+        for file_pattern in patterns {
+            files.extend(
+                expand_file_pattern(&file_pattern)?
+                    .into_iter()
+                    .map(|path| (path, None)),
+            );
+        }
+        files
     };
 
     if files.is_empty() {
@@ -122,38 +617,45 @@ fn main() -> Result<()> {
     let mut total_files = 0;
     let mut formatted_files = 0;
 
-    for file_pattern in files {
-        let file_paths = expand_file_pattern(&file_pattern)?;
+    for (file_path, lines) in files {
+        total_files += 1;
 
-        for file_path in file_paths {
-            total_files += 1;
-
-            if verbose {
-                println!("Processing: {}", file_path.blue());
-            }
+        if verbose {
+            println!("Processing: {}", file_path.blue());
+        }
 
-            match process_file(&mut formatter, &file_path, check_mode, write_mode) {
-                Ok(was_formatted) => {
-                    if was_formatted {
-                        formatted_files += 1;
-                        if check_mode {
-                            println!("{} {}", "✗".red(), file_path);
-                            needs_formatting = true;
-                        } else if write_mode {
-                            println!("{} {}", "✓".green(), file_path);
-                        }
-                    } else if verbose && check_mode {
+        match process_file(
+            &mut formatter,
+            cache.as_mut(),
+            &file_path,
+            lines.as_deref(),
+            matches!(diff_target, Some(DiffTarget::Staged)),
+            check_mode,
+            write_mode,
+        ) {
+            Ok(was_formatted) => {
+                if was_formatted {
+                    formatted_files += 1;
+                    if check_mode {
+                        println!("{} {}", "✗".red(), file_path);
+                        needs_formatting = true;
+                    } else if write_mode {
                         println!("{} {}", "✓".green(), file_path);
                     }
-                }
-                Err(e) => {
-                    eprintln!("{} {}: {}", "Error".red(), file_path, e);
-                    return Err(e);
+                } else if verbose && check_mode {
+                    println!("{} {}", "✓".green(), file_path);
                 }
             }
+            Err(e) => {
+                eprintln!("{} {}: {}", "Error".red(), file_path, e);
+                save_cache(cache.as_ref());
+                return Err(e);
+            }
         }
     }
 
+    save_cache(cache.as_ref());
+
     // Summary
     if verbose || check_mode {
         if check_mode && needs_formatting {
@@ -203,23 +705,496 @@ fn handle_stdin(formatter: &mut MustacheJson5Formatter, check_mode: bool) -> Res
     Ok(())
 }
 
+/// Read the template named by the `file` argument, where `-` is stdin
+fn read_template_arg(matches: &ArgMatches) -> Result<String> {
+    let file = matches
+        .get_one::<String>("file")
+        .map(String::as_str)
+        .unwrap_or("-");
+    if file == "-" {
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .context("Failed to read from stdin")?;
+        Ok(input)
+    } else {
+        fs::read_to_string(file).with_context(|| format!("Failed to read file: {}", file))
+    }
+}
+
+fn handle_parse(matches: &ArgMatches) -> Result<()> {
+    let source = read_template_arg(matches)?;
+
+    let tree = inspect::parse(&source)?;
+
+    if let Some(query_path) = matches.get_one::<String>("query") {
+        let query_source = fs::read_to_string(query_path)
+            .with_context(|| format!("Failed to read query file: {}", query_path))?;
+        print!(
+            "{}",
+            inspect::render_captures(&tree, &source, &query_source)?
+        );
+    } else {
+        let format = match matches.get_one::<String>("format").map(String::as_str) {
+            Some("outline") => TreeFormat::Outline,
+            Some("json") => TreeFormat::Json,
+            _ => TreeFormat::Sexp,
+        };
+        print!(
+            "{}",
+            inspect::render_tree(&tree, &source, format, format != TreeFormat::Json)?
+        );
+    }
+
+    let problems = inspect::problem_nodes(&tree);
+    if !problems.is_empty() {
+        for node in &problems {
+            let label = if node.is_missing() {
+                format!("MISSING {}", node.kind())
+            } else {
+                node.kind().to_string()
+            };
+            eprintln!(
+                "{} {} at {}:{}",
+                "✗".red(),
+                label.red(),
+                node.start_position().row + 1,
+                node.start_position().column + 1
+            );
+        }
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn handle_render(matches: &ArgMatches) -> Result<()> {
+    let source = read_template_arg(matches)?;
+
+    let data = match matches.get_one::<String>("data") {
+        Some(path) => {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read data file: {}", path))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse data file: {}", path))?
+        }
+        None => serde_json::Value::Object(Default::default()),
+    };
+
+    let mut renderer = MustacheJson5Renderer::new()?;
+    if let Some(escape) = matches.get_one::<String>("escape") {
+        renderer.set_escaper(escape.parse::<EscapeMode>()?);
+    }
+    add_partials(&mut renderer, matches)?;
+
+    let strict_json = matches.get_flag("strict-json");
+    let output = if strict_json || matches.get_flag("validate") {
+        renderer.render_validated(&source, &data, &ValidateOptions { strict_json })?
+    } else {
+        renderer.render(&source, &data)?
+    };
+    match matches.get_one::<String>("output") {
+        Some(path) => {
+            fs::write(path, output).with_context(|| format!("Failed to write file: {}", path))?
+        }
+        None => print!("{}", output),
+    }
+
+    Ok(())
+}
+
+/// Register `--partial NAME=PATH` arguments with `renderer`, and look up
+/// other partials under `--partials-dir`
+fn add_partials(renderer: &mut MustacheJson5Renderer, matches: &ArgMatches) -> Result<()> {
+    let mut dirs: Vec<PathBuf> = matches
+        .get_many::<String>("partials-dir")
+        .into_iter()
+        .flatten()
+        .map(PathBuf::from)
+        .collect();
+    if dirs.is_empty() {
+        if let Some(file) = matches
+            .get_one::<String>("file")
+            .filter(|file| *file != "-")
+        {
+            let dir = Path::new(file).parent().unwrap_or(Path::new(""));
+            dirs.push(if dir.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                dir.to_path_buf()
+            });
+        }
+    }
+    if !dirs.is_empty() {
+        renderer.set_resolver(FileSystemResolver::new(dirs));
+    }
+
+    for partial in matches.get_many::<String>("partial").into_iter().flatten() {
+        let (name, path) = partial
+            .split_once('=')
+            .with_context(|| format!("Expected NAME=PATH for --partial, got: {}", partial))?;
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read partial: {}", path))?;
+        renderer.add_partial(name, &content)?;
+    }
+    Ok(())
+}
+
+fn handle_analyze(matches: &ArgMatches) -> Result<()> {
+    let source = read_template_arg(matches)?;
+    let mut renderer = MustacheJson5Renderer::new()?;
+    add_partials(&mut renderer, matches)?;
+
+    let options = AnalyzeOptions {
+        max_combinations: *matches
+            .get_one::<usize>("max-combinations")
+            .expect("has a default"),
+        strict_json: matches.get_flag("strict-json"),
+    };
+    let analysis = renderer.analyze(&source, &options)?;
+
+    for finding in &analysis.findings {
+        eprintln!("{} {}", "✗".red(), finding);
+    }
+    if !analysis.is_complete() {
+        eprintln!(
+            "{} Checked {} of {} section combinations; raise --max-combinations to check all",
+            "!".yellow(),
+            analysis.checked,
+            analysis.total
+        );
+    }
+
+    if !analysis.is_valid() {
+        std::process::exit(1);
+    }
+    if matches.get_flag("verbose") {
+        println!(
+            "{} Valid for all {} section combinations",
+            "✓".green(),
+            analysis.checked
+        );
+    }
+    Ok(())
+}
+
+fn handle_schema(matches: &ArgMatches) -> Result<()> {
+    if let Some(("check", check_matches)) = matches.subcommand() {
+        let source = read_template_arg(check_matches)?;
+        let schema_path = check_matches.get_one::<String>("schema").expect("required");
+        let schema: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(schema_path)
+                .with_context(|| format!("Failed to read schema: {}", schema_path))?,
+        )
+        .with_context(|| format!("Failed to parse schema: {}", schema_path))?;
+
+        let mut renderer = MustacheJson5Renderer::new()?;
+        add_partials(&mut renderer, check_matches)?;
+        let issues = renderer.check_schema(&source, &schema)?;
+
+        let file = check_matches.get_one::<String>("file").expect("required");
+        for issue in &issues {
+            eprintln!("{} {}: {}", "✗".red(), file, issue);
+        }
+        if !issues.is_empty() {
+            std::process::exit(1);
+        }
+        if check_matches.get_flag("verbose") {
+            println!("{} {} matches the schema", "✓".green(), file);
+        }
+    }
+
+    if let Some(("infer", infer_matches)) = matches.subcommand() {
+        let source = read_template_arg(infer_matches)?;
+        let mut renderer = MustacheJson5Renderer::new()?;
+        add_partials(&mut renderer, infer_matches)?;
+        let shape = renderer.infer_shape(&source)?;
+
+        let name = infer_matches
+            .get_one::<String>("name")
+            .expect("has a default");
+        let output = match infer_matches
+            .get_one::<String>("format")
+            .map(String::as_str)
+        {
+            Some("typescript") => shape.to_typescript(name),
+            Some("rust") => shape.to_rust(name),
+            _ => format!(
+                "{}\n",
+                serde_json::to_string_pretty(&shape.to_json_schema())
+                    .context("Failed to serialize schema")?
+            ),
+        };
+
+        match infer_matches.get_one::<String>("output") {
+            Some(path) => fs::write(path, output)
+                .with_context(|| format!("Failed to write output: {}", path))?,
+            None => print!("{}", output),
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_lint(matches: &ArgMatches) -> Result<()> {
+    let mut linter = match matches.get_one::<String>("query") {
+        Some(path) => Linter::with_query(
+            &fs::read_to_string(path)
+                .with_context(|| format!("Failed to read query file: {}", path))?,
+        )?,
+        None => Linter::new()?,
+    };
+    let mut config = load_config(matches)?;
+    if let Some(max_depth) = matches.get_one::<u64>("max-depth") {
+        // The flag overrides the file's `max_depth` but keeps its severity
+        let rule = config.lint.rules.get("deep-nesting");
+        let severity = rule.and_then(RuleConfig::level);
+        let mut options = rule.map(RuleConfig::options).unwrap_or_default();
+        options.insert("max_depth".to_string(), (*max_depth).into());
+        config.lint.rules.insert(
+            "deep-nesting".to_string(),
+            RuleConfig::Options { severity, options },
+        );
+    }
+    linter.configure(&config.lint)?;
+    linter.forbid_trailing_commas(matches!(config.trailing_commas, TrailingCommaStyle::Never));
+
+    let patterns: Vec<String> = match matches.get_many::<String>("files") {
+        Some(files) => files.cloned().collect(),
+        None => find_mustache_json5_files(".")?,
+    };
+    let mut files = Vec::new();
+    for pattern in patterns {
+        if pattern == "-" {
+            files.push(pattern);
+        } else {
+            files.extend(expand_file_pattern(&pattern)?);
+        }
+    }
+
+    let fix = matches.get_flag("fix");
+    if fix && files.iter().any(|file| file == "-") {
+        anyhow::bail!("--fix cannot be used with stdin");
+    }
+
+    let json = matches.get_one::<String>("format").map(String::as_str) == Some("json");
+    let mut report = Vec::new();
+    let (mut errors, mut warnings) = (0, 0);
+    for file in &files {
+        let source = if file == "-" {
+            let mut input = String::new();
+            io::stdin()
+                .read_to_string(&mut input)
+                .context("Failed to read from stdin")?;
+            input
+        } else {
+            fs::read_to_string(file).with_context(|| format!("Failed to read file: {}", file))?
+        };
+
+        let diagnostics = if fix {
+            let fixed = linter.fix(&source)?;
+            if fixed.fixed > 0 {
+                fs::write(file, &fixed.source)
+                    .with_context(|| format!("Failed to write file: {}", file))?;
+                eprintln!("{} Fixed {} problems in {}", "✓".green(), fixed.fixed, file);
+            }
+            fixed.diagnostics
+        } else {
+            linter.lint(&source)?
+        };
+
+        for diagnostic in diagnostics {
+            match diagnostic.severity {
+                Severity::Error => errors += 1,
+                Severity::Warning => warnings += 1,
+            }
+            if json {
+                report.push(serde_json::json!({ "file": file, "diagnostic": diagnostic }));
+            } else {
+                let severity = match diagnostic.severity {
+                    Severity::Error => "error".red(),
+                    Severity::Warning => "warning".yellow(),
+                };
+                println!(
+                    "{}:{}:{}: {}[{}]: {}",
+                    file,
+                    diagnostic.line,
+                    diagnostic.column,
+                    severity,
+                    diagnostic.rule,
+                    diagnostic.message
+                );
+            }
+        }
+    }
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).context("Failed to serialize diagnostics")?
+        );
+    } else if errors + warnings > 0 || matches.get_flag("verbose") {
+        eprintln!(
+            "\n{} {} errors and {} warnings in {} files",
+            if errors > 0 {
+                "✗".red()
+            } else {
+                "✓".green()
+            },
+            errors,
+            warnings,
+            files.len()
+        );
+    }
+
+    if errors > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn handle_init_config(matches: &ArgMatches) -> Result<()> {
+    let paths: Vec<String> = matches
+        .get_many::<String>("paths")
+        .map(|paths| paths.cloned().collect())
+        .unwrap_or_else(|| vec![".".to_string()]);
+
+    let mut stats = StyleStats::default();
+    let mut scanned = 0;
+    for path in paths {
+        let files = if Path::new(&path).is_dir() {
+            find_mustache_json5_files(&path)?
+        } else {
+            vec![path]
+        };
+
+        for file in files {
+            let source = fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read file: {}", file))?;
+            stats
+                .add_source(&source)
+                .with_context(|| format!("Failed to parse file: {}", file))?;
+            scanned += 1;
+        }
+    }
+
+    if scanned == 0 {
+        eprintln!(
+            "{}",
+            "No mustache_json5 files found, using the default configuration".yellow()
+        );
+    }
+    let config = stats.to_config();
+
+    if matches.get_flag("stdout") {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&config).context("Failed to serialize config")?
+        );
+        return Ok(());
+    }
+
+    let output = matches
+        .get_one::<String>("output")
+        .map(String::as_str)
+        .unwrap_or(".mustache-json5-fmt.json");
+    if Path::new(output).exists() && !matches.get_flag("force") {
+        anyhow::bail!("{} already exists; use --force to overwrite it", output);
+    }
+
+    config.to_file(output)?;
+    println!(
+        "{} Wrote {} (inferred from {} files)",
+        "✓".green(),
+        output,
+        scanned
+    );
+
+    Ok(())
+}
+
+fn handle_hook(matches: &ArgMatches) -> Result<()> {
+    if let Some(("install", install_matches)) = matches.subcommand() {
+        let options = HookOptions {
+            fix: install_matches.get_flag("fix"),
+            force: install_matches.get_flag("force"),
+        };
+        let hook_path = hook::install(Path::new("."), &options)?;
+        println!("{} Installed {}", "✓".green(), hook_path.display());
+    }
+
+    Ok(())
+}
+
+fn handle_watch(dir: &Path, config: Config, options: &WatchOptions, verbose: bool) -> Result<()> {
+    println!(
+        "Watching {} for changes...",
+        dir.display().to_string().blue()
+    );
+
+    watch::watch(dir, config, options, |event| match event {
+        WatchEvent::Formatted(path) => println!("{} {}", "✓".green(), path.display()),
+        WatchEvent::NeedsFormatting(path) => println!("{} {}", "✗".red(), path.display()),
+        WatchEvent::Unchanged(path) => {
+            if verbose {
+                println!("{} {}", "✓".green(), path.display());
+            }
+        }
+        WatchEvent::Error(path, e) => {
+            eprintln!("{} {}: {:#}", "Error".red(), path.display(), e);
+        }
+    })
+}
+
 fn process_file(
     formatter: &mut MustacheJson5Formatter,
+    cache: Option<&mut FormatCache>,
     file_path: &str,
+    lines: Option<&[Range<usize>]>,
+    staged: bool,
     check_mode: bool,
     write_mode: bool,
 ) -> Result<bool> {
-    let original_content = fs::read_to_string(file_path)
-        .with_context(|| format!("Failed to read file: {}", file_path))?;
+    // With --staged, the index is what gets committed, so that is what is formatted
+    let original_content = if staged {
+        git::staged_contents(Path::new(file_path))?
+    } else {
+        fs::read_to_string(file_path)
+            .with_context(|| format!("Failed to read file: {}", file_path))?
+    };
 
-    let formatted_content = formatter
-        .format(&original_content)
-        .with_context(|| format!("Failed to format file: {}", file_path))?;
+    if cache
+        .as_ref()
+        .is_some_and(|cache| cache.is_formatted(file_path, &original_content))
+    {
+        return Ok(false);
+    }
+
+    let formatted_content = match lines {
+        Some(lines) => formatter.format_lines(&original_content, lines),
+        None => formatter.format(&original_content),
+    }
+    .with_context(|| format!("Failed to format file: {}", file_path))?;
 
     let needs_formatting = original_content != formatted_content;
 
     if needs_formatting {
-        if write_mode {
+        if write_mode && staged {
+            git::stage_contents(Path::new(file_path), &formatted_content)?;
+            // Unstaged changes in the working tree are left for the next commit
+            let working_content = fs::read_to_string(file_path)
+                .with_context(|| format!("Failed to read file: {}", file_path))?;
+            if working_content == original_content {
+                fs::write(file_path, &formatted_content)
+                    .with_context(|| format!("Failed to write file: {}", file_path))?;
+            } else {
+                eprintln!(
+                    "{} {}: formatted the staged version only; unstaged changes were left as they are",
+                    "Warning:".yellow(),
+                    file_path
+                );
+            }
+        } else if write_mode {
             fs::write(file_path, &formatted_content)
                 .with_context(|| format!("Failed to write file: {}", file_path))?;
         } else if !check_mode {
@@ -228,9 +1203,20 @@ fn process_file(
         }
     }
 
+    // Rewritten files are only cached once a later run confirms they are stable
+    if let (Some(cache), false) = (cache, needs_formatting) {
+        cache.mark_formatted(file_path, &original_content);
+    }
+
     Ok(needs_formatting)
 }
 
+fn save_cache(cache: Option<&FormatCache>) {
+    if let Some(Err(e)) = cache.map(FormatCache::save) {
+        eprintln!("{} {:#}", "Warning: failed to save cache:".yellow(), e);
+    }
+}
+
 fn find_mustache_json5_files(dir: &str) -> Result<Vec<String>> {
     let mut files = Vec::new();
 
@@ -240,11 +1226,9 @@ fn find_mustache_json5_files(dir: &str) -> Result<Vec<String>> {
         .filter_map(|e| e.ok())
     {
         let path = entry.path();
-        if let Some(extension) = path.extension() {
-            if extension == "mustache_json5" || extension == "mjson5" {
-                if let Some(path_str) = path.to_str() {
-                    files.push(path_str.to_string());
-                }
+        if is_template_file(path) {
+            if let Some(path_str) = path.to_str() {
+                files.push(path_str.to_string());
             }
         }
     }
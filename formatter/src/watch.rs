@@ -0,0 +1,181 @@
+//! Watch a directory and format templates as they are saved
+//!
+//! Filesystem notifications are collected until the directory has been quiet
+//! for the debounce interval, so an editor that writes a file in several steps
+//! (truncate, write, rename) only triggers one format per file.
+
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::formatter::MustacheJson5Formatter;
+
+/// Options controlling how changed files are handled
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Only report files that need formatting instead of rewriting them
+    pub check: bool,
+
+    /// How long the directory must be quiet before changed files are processed
+    pub debounce: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            check: false,
+            debounce: Duration::from_millis(200),
+        }
+    }
+}
+
+/// Outcome of processing a single changed file
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// The file was rewritten with formatted content
+    Formatted(PathBuf),
+    /// The file is not formatted (check mode only)
+    NeedsFormatting(PathBuf),
+    /// The file was already formatted
+    Unchanged(PathBuf),
+    /// The file could not be read, formatted or written
+    Error(PathBuf, anyhow::Error),
+}
+
+/// Watch `dir` recursively and format changed template files until the
+/// watcher shuts down, reporting each processed file to `on_event`.
+///
+/// A single formatter is created up front and reused for every event.
+pub fn watch<F>(dir: &Path, config: Config, options: &WatchOptions, mut on_event: F) -> Result<()>
+where
+    F: FnMut(WatchEvent),
+{
+    let mut formatter = MustacheJson5Formatter::new(config)?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("Failed to create file watcher")?;
+    watcher
+        .watch(dir, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch directory: {}", dir.display()))?;
+
+    // Content we wrote ourselves, so the resulting notification is ignored
+    let mut written: HashMap<PathBuf, String> = HashMap::new();
+
+    while let Ok(first) = rx.recv() {
+        let mut changed = BTreeSet::new();
+        collect_paths(first, &mut changed);
+
+        // Drain the burst until the directory has been quiet long enough
+        while let Ok(next) = rx.recv_timeout(options.debounce) {
+            collect_paths(next, &mut changed);
+        }
+
+        for path in changed {
+            if let Some(event) = process_change(&mut formatter, &path, options, &mut written) {
+                on_event(event);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn collect_paths(event: notify::Result<notify::Event>, changed: &mut BTreeSet<PathBuf>) {
+    let Ok(event) = event else {
+        return;
+    };
+
+    if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+        return;
+    }
+
+    changed.extend(
+        event
+            .paths
+            .into_iter()
+            .filter(|path| crate::is_template_file(path)),
+    );
+}
+
+fn process_change(
+    formatter: &mut MustacheJson5Formatter,
+    path: &Path,
+    options: &WatchOptions,
+    written: &mut HashMap<PathBuf, String>,
+) -> Option<WatchEvent> {
+    // Removed or renamed away before we got to it
+    let content = fs::read_to_string(path).ok()?;
+
+    if written.get(path) == Some(&content) {
+        return None;
+    }
+    written.remove(path);
+
+    let formatted = match formatter.format(&content) {
+        Ok(formatted) => formatted,
+        Err(e) => return Some(WatchEvent::Error(path.to_path_buf(), e)),
+    };
+
+    if formatted == content {
+        return Some(WatchEvent::Unchanged(path.to_path_buf()));
+    }
+
+    if options.check {
+        return Some(WatchEvent::NeedsFormatting(path.to_path_buf()));
+    }
+
+    if let Err(e) = fs::write(path, &formatted) {
+        let error = anyhow::Error::new(e).context("Failed to write file");
+        return Some(WatchEvent::Error(path.to_path_buf(), error));
+    }
+
+    written.insert(path.to_path_buf(), formatted);
+    Some(WatchEvent::Formatted(path.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_process_change_writes_formatted_content() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.mjson5");
+        fs::write(&path, r#"{"name":"test","value":1}"#).unwrap();
+
+        let mut formatter = MustacheJson5Formatter::new(Config::default()).unwrap();
+        let mut written = HashMap::new();
+        let options = WatchOptions::default();
+
+        let event = process_change(&mut formatter, &path, &options, &mut written);
+        assert!(matches!(event, Some(WatchEvent::Formatted(_))));
+
+        // The notification caused by our own write is ignored
+        let event = process_change(&mut formatter, &path, &options, &mut written);
+        assert!(event.is_none());
+    }
+
+    #[test]
+    fn test_process_change_check_mode_does_not_write() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.mjson5");
+        let input = r#"{"name":"test","value":1}"#;
+        fs::write(&path, input).unwrap();
+
+        let mut formatter = MustacheJson5Formatter::new(Config::default()).unwrap();
+        let options = WatchOptions {
+            check: true,
+            ..WatchOptions::default()
+        };
+
+        let event = process_change(&mut formatter, &path, &options, &mut HashMap::new());
+        assert!(matches!(event, Some(WatchEvent::NeedsFormatting(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), input);
+    }
+}
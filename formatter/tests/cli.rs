@@ -0,0 +1,88 @@
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn fmt(dir: &TempDir, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_mustache-json5-fmt"))
+        .args(args)
+        .current_dir(dir.path())
+        .output()
+        .expect("Failed to run mustache-json5-fmt")
+}
+
+#[test]
+fn test_flag_form_formats_a_file_named_check() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("check"), r#"{"a":1,"b":2}"#).unwrap();
+
+    let output = fmt(&dir, &["--check", "./check"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    for args in [&["--write", "./check"][..], &["--write", "--", "check"][..]] {
+        fs::write(dir.path().join("check"), r#"{"a":1,"b":2}"#).unwrap();
+        assert!(fmt(&dir, args).status.success());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("check")).unwrap(),
+            "{\n  \"a\": 1,\n  \"b\": 2\n}"
+        );
+    }
+}
+
+#[test]
+fn test_options_before_the_subcommand() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("a.mjson5"), r#"{"a":1,"b":2}"#).unwrap();
+
+    let output = fmt(&dir, &["--verbose", "fmt", "a.mjson5"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\n  \"a\": 1"));
+
+    let output = fmt(&dir, &["--indent-size", "4", "-v", "check", "a.mjson5"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    // Options the subcommand does not have are rejected, not ignored
+    let output = fmt(&dir, &["--write", "check", "a.mjson5"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_flag_form_ignores_config_files_it_was_not_given() {
+    let dir = TempDir::new().unwrap();
+    let mut config = serde_json::to_value(mustache_json5_fmt::Config::default()).unwrap();
+    config["indent_size"] = 4.into();
+    fs::write(
+        dir.path().join(".mustache-json5-fmt.json"),
+        config.to_string(),
+    )
+    .unwrap();
+    fs::write(dir.path().join("a.mjson5"), r#"{"a":1,"b":2}"#).unwrap();
+
+    let output = fmt(&dir, &["a.mjson5"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\n  \"a\": 1"));
+
+    let output = fmt(&dir, &["fmt", "a.mjson5"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\n    \"a\": 1"));
+
+    let output = fmt(&dir, &["--config", ".mustache-json5-fmt.json", "a.mjson5"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\n    \"a\": 1"));
+}
+
+#[test]
+fn test_lint_max_depth_keeps_the_configured_severity() {
+    let dir = TempDir::new().unwrap();
+    let mut config = serde_json::to_value(mustache_json5_fmt::Config::default()).unwrap();
+    config["lint"] = serde_json::json!({"rules": {"deep-nesting": "error"}});
+    fs::write(
+        dir.path().join(".mustache-json5-fmt.json"),
+        config.to_string(),
+    )
+    .unwrap();
+    fs::write(dir.path().join("a.mjson5"), "[{{#x}}{{#y}}1{{/y}}{{/x}}]").unwrap();
+
+    let output = fmt(&dir, &["lint", "--max-depth", "1", "a.mjson5"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("error[deep-nesting]"));
+
+    let output = fmt(&dir, &["lint", "--max-depth", "0", "a.mjson5"]);
+    assert_eq!(output.status.code(), Some(2));
+}
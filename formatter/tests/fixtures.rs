@@ -328,6 +328,7 @@ pub fn all_test_cases() -> Vec<&'static TestCase> {
     all_tests
 }
 
+#[allow(dead_code)]
 pub fn write_test_files() -> std::io::Result<()> {
     let test_dir = Path::new("test_files");
     fs::create_dir_all(test_dir)?;
@@ -14,7 +14,7 @@ fn test_basic_json5_formatting() {
     for test_case in BASIC_JSON5_TESTS {
         let result = formatter
             .format(test_case.input)
-            .expect(&format!("Failed to format test case: {}", test_case.name));
+            .unwrap_or_else(|_| panic!("Failed to format test case: {}", test_case.name));
 
         assert_eq!(
             result.trim(),
@@ -34,7 +34,7 @@ fn test_mustache_formatting() {
     for test_case in MUSTACHE_TESTS {
         let result = formatter
             .format(test_case.input)
-            .expect(&format!("Failed to format test case: {}", test_case.name));
+            .unwrap_or_else(|_| panic!("Failed to format test case: {}", test_case.name));
 
         assert_eq!(
             result.trim(),
@@ -54,7 +54,7 @@ fn test_mixed_content_formatting() {
     for test_case in MIXED_CONTENT_TESTS {
         let result = formatter
             .format(test_case.input)
-            .expect(&format!("Failed to format test case: {}", test_case.name));
+            .unwrap_or_else(|_| panic!("Failed to format test case: {}", test_case.name));
 
         assert_eq!(
             result.trim(),
@@ -74,7 +74,7 @@ fn test_comment_preservation() {
     for test_case in COMMENT_TESTS {
         let result = formatter
             .format(test_case.input)
-            .expect(&format!("Failed to format test case: {}", test_case.name));
+            .unwrap_or_else(|_| panic!("Failed to format test case: {}", test_case.name));
 
         assert_eq!(
             result.trim(),
@@ -94,7 +94,7 @@ fn test_edge_cases() {
     for test_case in EDGE_CASE_TESTS {
         let result = formatter
             .format(test_case.input)
-            .expect(&format!("Failed to format test case: {}", test_case.name));
+            .unwrap_or_else(|_| panic!("Failed to format test case: {}", test_case.name));
 
         assert_eq!(
             result.trim(),
@@ -114,7 +114,7 @@ fn test_real_world_examples() {
     for test_case in REAL_WORLD_TESTS {
         let result = formatter
             .format(test_case.input)
-            .expect(&format!("Failed to format test case: {}", test_case.name));
+            .unwrap_or_else(|_| panic!("Failed to format test case: {}", test_case.name));
 
         assert_eq!(
             result.trim(),
@@ -129,8 +129,10 @@ fn test_real_world_examples() {
 #[test]
 fn test_configuration_options() {
     // Test with tabs
-    let mut config = Config::default();
-    config.use_tabs = true;
+    let config = Config {
+        use_tabs: true,
+        ..Config::default()
+    };
     let mut formatter = MustacheJson5Formatter::new(config).expect("Failed to create formatter");
 
     let input = r#"{"name":"test","value":123}"#;
@@ -138,8 +140,10 @@ fn test_configuration_options() {
     assert!(result.contains('\t'), "Expected tabs in output");
 
     // Test with different indent size
-    let mut config = Config::default();
-    config.indent_size = 4;
+    let config = Config {
+        indent_size: 4,
+        ..Config::default()
+    };
     let mut formatter = MustacheJson5Formatter::new(config).expect("Failed to create formatter");
 
     let result = formatter
@@ -170,12 +174,11 @@ fn test_idempotency() {
     for test_case in all_test_cases() {
         let first_pass = formatter
             .format(test_case.input)
-            .expect(&format!("Failed first format pass for: {}", test_case.name));
+            .unwrap_or_else(|_| panic!("Failed first format pass for: {}", test_case.name));
 
-        let second_pass = formatter.format(&first_pass).expect(&format!(
-            "Failed second format pass for: {}",
-            test_case.name
-        ));
+        let second_pass = formatter
+            .format(&first_pass)
+            .unwrap_or_else(|_| panic!("Failed second format pass for: {}", test_case.name));
 
         assert_eq!(
             first_pass, second_pass,
@@ -0,0 +1,84 @@
+//! Typed render contexts for Mustache JSON5 templates
+//!
+//! `#[template_context(path = "...")]` turns a unit struct into the context
+//! a template expects, with nested structs for the objects it uses, and
+//! implements `mustache_json5_render::TemplateContext` for it. The path is
+//! relative to the crate's `Cargo.toml`; partials are looked up next to the
+//! template.
+//!
+//! ```ignore
+//! use mustache_json5_render::TemplateContext;
+//!
+//! #[mustache_json5_macros::template_context(path = "templates/profile.mjson5")]
+//! pub struct Profile;
+//!
+//! let output = Profile {
+//!     user: ProfileUser { name: "Ann".into(), ..Default::default() },
+//!     ..Default::default()
+//! }
+//! .render()?;
+//! ```
+//!
+//! The generated structs derive `serde::Serialize` and `serde::Deserialize`,
+//! so the crate using them needs `serde` with the `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use std::path::PathBuf;
+use syn::{parse_macro_input, Fields, Item, ItemStruct, LitStr};
+
+#[proc_macro_attribute]
+pub fn template_context(args: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemStruct);
+    let mut path: Option<LitStr> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("path") {
+            path = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `path = \"...\"`"))
+        }
+    });
+    parse_macro_input!(args with parser);
+
+    let Some(path) = path else {
+        return syn::Error::new(Span::call_site(), "expected `path = \"...\"`")
+            .to_compile_error()
+            .into();
+    };
+    expand(item, path)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+fn expand(item: ItemStruct, path: LitStr) -> syn::Result<proc_macro2::TokenStream> {
+    if !matches!(item.fields, Fields::Unit) || !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item,
+            "`#[template_context]` generates the fields, so use a unit struct: `struct Name;`",
+        ));
+    }
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let template = PathBuf::from(manifest_dir).join(path.value());
+    let code =
+        mustache_json5_render::codegen::context_code(&template, &item.ident.to_string(), true)
+            .map_err(|error| syn::Error::new(path.span(), format!("{:#}", error)))?;
+    let file: syn::File =
+        syn::parse_str(&code).map_err(|error| syn::Error::new(path.span(), error.to_string()))?;
+
+    let items = file.items.into_iter().map(|generated| match generated {
+        Item::Struct(mut generated) => {
+            generated.vis = item.vis.clone();
+            if generated.ident == item.ident {
+                let mut attrs = item.attrs.clone();
+                attrs.append(&mut generated.attrs);
+                generated.attrs = attrs;
+            }
+            Item::Struct(generated)
+        }
+        other => other,
+    });
+    Ok(quote!(#(#items)*))
+}
@@ -0,0 +1,51 @@
+use mustache_json5_macros::template_context;
+use mustache_json5_render::TemplateContext;
+use serde_json::json;
+
+/// Data for the profile endpoint
+#[template_context(path = "tests/templates/profile.mjson5")]
+pub struct Profile;
+
+#[test]
+fn test_generated_context_renders() {
+    let profile = Profile {
+        user: ProfileUser {
+            name: json!("Ann \"A\""),
+            roles: vec![
+                ProfileRole { id: json!("admin") },
+                ProfileRole {
+                    id: json!("editor"),
+                },
+            ],
+        },
+        active: Some(true),
+    };
+
+    let output = profile.render().unwrap();
+    let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(
+        value,
+        json!({"name": "Ann \"A\"", "roles": ["admin", "editor"], "active": true})
+    );
+}
+
+#[test]
+fn test_scalars_accept_numbers_and_booleans() {
+    let profile: Profile = serde_json::from_value(json!({
+        "user": {"name": 3, "roles": [{"id": true}]}
+    }))
+    .unwrap();
+    assert_eq!(profile.user.name, json!(3));
+    assert_eq!(profile.user.roles[0].id, json!(true));
+}
+
+#[test]
+fn test_optional_fields_default_to_none() {
+    let profile = Profile::default();
+    assert_eq!(profile.active, None);
+    assert_eq!(
+        serde_json::to_value(&profile).unwrap()["user"]["roles"],
+        json!([])
+    );
+    assert!(Profile::PARTIALS.iter().any(|(name, _)| *name == "footer"));
+}
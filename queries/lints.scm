@@ -1,21 +1,15 @@
 ; Linting rules for common mustache_json5 issues
+;
+; Checks that need more than pattern matching (mismatched section names,
+; unused and shadowed parameters, duplicate keys, unreachable sections,
+; nesting depth and unsafe unescaped output) are implemented in Rust by the
+; formatter's `lint` module.
 
 ; Error: Unclosed mustache sections
 (mustache_section_begin
   (tag_name) @error.unclosed.section
   (#not-has-ancestor? @error.unclosed.section mustache_section))
 
-; Error: Mismatched section tags
-(mustache_section
-  (mustache_section_begin (tag_name) @start.tag)
-  (mustache_section_end (tag_name) @end.tag)
-  (#not-eq? @start.tag @end.tag)) @error.mismatched.section
-
-(mustache_inverted_section
-  (mustache_inverted_section_begin (tag_name) @start.tag)
-  (mustache_inverted_section_end (tag_name) @end.tag)
-  (#not-eq? @start.tag @end.tag)) @error.mismatched.inverted
-
 ; Warning: Empty mustache expressions
 ((mustache_interpolation) @warning.empty.expression
   (#match? @warning.empty.expression "^\\{\\{\\s*\\}\\}$"))
@@ -25,39 +19,27 @@
   (identifier_expression) @warning.deprecated
   (#match? @warning.deprecated "^(\\$|@|_)"))
 
-; Error: Invalid JSON5 trailing commas in wrong places
-((object
-  ","
-  "}") @error.trailing.comma)
-
-((array
-  ","
-  "]") @error.trailing.comma)
+; Warning: Trailing commas, which JSON5 allows but plain JSON does not; the
+; linter raises these to errors with a fix when `trailing_commas` is `never`
+(object
+  "," @warning.trailing.comma
+  .
+  "}")
 
-; Warning: Potentially unsafe unescaped output
-(mustache_unescaped
-  (identifier_expression) @warning.unsafe.unescaped
-  (#match? @warning.unsafe.unescaped "(html|script|style|user|input)"))
+(array
+  "," @warning.trailing.comma
+  .
+  "]")
 
 ; Error: Invalid mustache section parameters syntax
 ((section_parameters) @error.invalid.params
   (#not-match? @error.invalid.params "^\\s*as\\s*\\|.*\\|\\s*$"))
 
-; Warning: Unused section parameters
-((section_parameters
-  (parameter) @warning.unused.param))
-
 ; Error: Invalid path expressions (starting with dot)
 (path_expression
   (identifier_expression) @error.invalid.path
   (#match? @error.invalid.path "^\\."))
 
-; Warning: Deep nesting (more than 3 levels)
-(mustache_section
-  (mustache_section
-    (mustache_section
-      (mustache_section) @warning.deep.nesting)))
-
 ; Error: Missing required closing delimiters
 ((ERROR) @error.unclosed.delimiter
   (#match? @error.unclosed.delimiter "\\{\\{[^}]*$"))
@@ -95,6 +77,12 @@
   (#any-of? @error.reserved.keyword "if" "unless" "each" "with" "unless" "else"))
 
 ; Warning: Empty JSON5 objects or arrays
-((object) @warning.empty.object)
-
-((array) @warning.empty.array)
+(object
+  "{" @warning.empty.object
+  .
+  "}")
+
+(array
+  "[" @warning.empty.array
+  .
+  "]")
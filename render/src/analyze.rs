@@ -0,0 +1,492 @@
+//! Check that every rendering of a template is valid, without data
+//!
+//! Each section subject (the name a section tests, like `items` in
+//! `{{#each items}}`) is set off, on once, or repeated twice, and the template
+//! is expanded for every combination with placeholder values. Loop variables
+//! such as `@last` follow the iteration, so separator logic like
+//! `{{#unless @last}},{{/unless}}` is checked properly.
+//!
+//! Failing combinations are reduced to the fewest section states that make
+//! the document invalid whatever the other sections do.
+
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+use crate::renderer::{MustacheJson5Renderer, Rendered, Segment, MAX_PARTIAL_DEPTH};
+use crate::template::{Node, Placement, Section, Template};
+use crate::validate::{self, ValidateOptions, ValidationError};
+
+/// Options for [`MustacheJson5Renderer::analyze`]
+#[derive(Debug, Clone, Copy)]
+pub struct AnalyzeOptions {
+    /// Stop after this many section combinations
+    pub max_combinations: usize,
+    /// Require plain JSON rather than JSON5
+    pub strict_json: bool,
+}
+
+impl Default for AnalyzeOptions {
+    fn default() -> Self {
+        Self {
+            max_combinations: 4096,
+            strict_json: false,
+        }
+    }
+}
+
+/// The state assumed for a section subject
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SectionState {
+    /// Missing, false or empty
+    Off,
+    /// Truthy, or a list with one item
+    Once,
+    /// A list with two items
+    Repeated,
+}
+
+impl fmt::Display for SectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SectionState::Off => "off",
+            SectionState::Once => "on",
+            SectionState::Repeated => "repeated",
+        })
+    }
+}
+
+/// A name tested by one or more sections
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subject {
+    pub name: String,
+    /// Whether any section iterates it, so repetition is worth checking
+    pub iterates: bool,
+}
+
+/// Section states that always produce an invalid document
+#[derive(Debug, Clone)]
+pub struct Finding {
+    /// The minimal states responsible; empty if every combination fails
+    pub states: Vec<(String, SectionState)>,
+    /// The error for one such combination
+    pub error: ValidationError,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.states.is_empty() {
+            return write!(f, "in every state: {}", self.error);
+        }
+        let states: Vec<String> = self
+            .states
+            .iter()
+            .map(|(name, state)| format!("`{}` {}", name, state))
+            .collect();
+        write!(f, "with {}: {}", states.join(", "), self.error)
+    }
+}
+
+/// Result of [`MustacheJson5Renderer::analyze`]
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub subjects: Vec<Subject>,
+    /// Combinations checked, and how many there are in total
+    pub checked: usize,
+    pub total: usize,
+    pub findings: Vec<Finding>,
+}
+
+impl Analysis {
+    /// Whether every combination was checked
+    pub fn is_complete(&self) -> bool {
+        self.checked == self.total
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl MustacheJson5Renderer {
+    /// Check every combination of section states in `source`
+    pub fn analyze(&mut self, source: &str, options: &AnalyzeOptions) -> Result<Analysis> {
+        let template = self.compile(source)?;
+        self.analyze_template(&template, options)
+    }
+
+    pub fn analyze_template(
+        &self,
+        template: &Template,
+        options: &AnalyzeOptions,
+    ) -> Result<Analysis> {
+        let mut subjects = Vec::new();
+        self.collect_subjects(template.nodes(), &mut subjects, 0);
+        let index: HashMap<String, usize> = subjects
+            .iter()
+            .enumerate()
+            .map(|(i, subject)| (subject.name.clone(), i))
+            .collect();
+
+        let radix: Vec<usize> = subjects
+            .iter()
+            .map(|subject| if subject.iterates { 3 } else { 2 })
+            .collect();
+        let total = radix
+            .iter()
+            .try_fold(1usize, |total, &r| total.checked_mul(r))
+            .unwrap_or(usize::MAX);
+        let checked = total.min(options.max_combinations.max(1));
+
+        let validate_options = ValidateOptions {
+            strict_json: options.strict_json,
+        };
+        let combinations: Vec<Vec<SectionState>> =
+            (0..checked).map(|k| decode(k, &radix)).collect();
+        let mut errors = Vec::with_capacity(checked);
+        for states in &combinations {
+            let rendered = self.expand(template, &index, states)?;
+            errors.push(validate::check(&rendered.output, &validate_options).err());
+        }
+
+        let mut patterns: Vec<Vec<Option<SectionState>>> = Vec::new();
+        let mut findings = Vec::new();
+        for (k, error) in errors.iter().enumerate() {
+            let Some(error) = error else { continue };
+            let states = &combinations[k];
+            if patterns.iter().any(|pattern| matches(pattern, states)) {
+                continue;
+            }
+
+            // Drop each state that the failure does not depend on
+            let mut pattern: Vec<Option<SectionState>> = states.iter().copied().map(Some).collect();
+            for i in 0..pattern.len() {
+                let mut candidate = pattern.clone();
+                candidate[i] = None;
+                let always_fails = combinations
+                    .iter()
+                    .zip(&errors)
+                    .filter(|(states, _)| matches(&candidate, states))
+                    .all(|(_, error)| error.is_some());
+                if always_fails {
+                    pattern = candidate;
+                }
+            }
+
+            let rendered = self.expand(template, &index, states)?;
+            findings.push(Finding {
+                states: pattern
+                    .iter()
+                    .zip(&subjects)
+                    .filter_map(|(state, subject)| state.map(|state| (subject.name.clone(), state)))
+                    .collect(),
+                error: self.locate(template, &rendered, error.clone()),
+            });
+            patterns.push(pattern);
+        }
+
+        Ok(Analysis {
+            subjects,
+            checked,
+            total,
+            findings,
+        })
+    }
+
+    fn collect_subjects(&self, nodes: &[Node], subjects: &mut Vec<Subject>, depth: usize) {
+        for node in nodes {
+            match node {
+                Node::Section(section) => {
+                    let name = section.path().to_string();
+                    let iterates = !section.inverted
+                        && (section.helper() == Some("each") || section.expression.is_none());
+                    if !name.starts_with('@') {
+                        match subjects.iter_mut().find(|subject| subject.name == name) {
+                            Some(subject) => subject.iterates |= iterates,
+                            None => subjects.push(Subject { name, iterates }),
+                        }
+                    }
+                    self.collect_subjects(&section.body, subjects, depth);
+                }
+                Node::Partial(partial) if depth < MAX_PARTIAL_DEPTH => {
+                    if let Some(included) = self.partial(&partial.name) {
+                        self.collect_subjects(included.nodes(), subjects, depth + 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Expand `template` for one combination of section states
+    fn expand(
+        &self,
+        template: &Template,
+        index: &HashMap<String, usize>,
+        states: &[SectionState],
+    ) -> Result<Rendered> {
+        let mut expander = Expander {
+            renderer: self,
+            index,
+            states,
+            rendered: Rendered::default(),
+            partial: None,
+            section: None,
+            loops: Vec::new(),
+        };
+        expander.nodes(template, template.nodes(), 0)?;
+        Ok(expander.rendered)
+    }
+}
+
+struct Expander<'a> {
+    renderer: &'a MustacheJson5Renderer,
+    index: &'a HashMap<String, usize>,
+    states: &'a [SectionState],
+    rendered: Rendered,
+    partial: Option<String>,
+    section: Option<Range<usize>>,
+    /// Index and length of each enclosing loop
+    loops: Vec<(usize, usize)>,
+}
+
+impl Expander<'_> {
+    fn push(&mut self, text: &str, source: Range<usize>, variable: bool) {
+        let start = self.rendered.output.len();
+        self.rendered.output.push_str(text);
+        if !text.is_empty() {
+            self.rendered.segments.push(Segment {
+                output: start..self.rendered.output.len(),
+                partial: self.partial.clone(),
+                source,
+                variable,
+                section: self.section.clone(),
+            });
+        }
+    }
+
+    fn nodes(&mut self, template: &Template, nodes: &[Node], depth: usize) -> Result<()> {
+        for node in nodes {
+            match node {
+                Node::Text(range) => {
+                    self.push(&template.source()[range.clone()], range.clone(), false)
+                }
+                Node::Comment(_) => {}
+                // Placeholders that are valid wherever JSON escaping puts a value
+                Node::Variable(variable) => {
+                    let placeholder = match variable.placement {
+                        // Self-delimiting, so adjacent values stay separate tokens
+                        Placement::Value => "[]",
+                        Placement::Key => "\"k\"",
+                        Placement::String { .. } => "x",
+                    };
+                    self.push(placeholder, variable.span.clone(), true);
+                }
+                Node::Section(section) => {
+                    let outer = self.section.replace(section.open.clone());
+                    let result = self.section(template, section, depth);
+                    self.section = outer;
+                    result?;
+                }
+                Node::Partial(partial) => {
+                    if depth >= MAX_PARTIAL_DEPTH {
+                        bail!(
+                            "Partial `{}` exceeds the maximum nesting depth of {}",
+                            partial.name,
+                            MAX_PARTIAL_DEPTH
+                        );
+                    }
+                    if let Some(included) = self.renderer.partial(&partial.name) {
+                        let outer_partial = self.partial.replace(partial.name.clone());
+                        let outer_section = self.section.take();
+                        let result = self.nodes(included, included.nodes(), depth + 1);
+                        self.partial = outer_partial;
+                        self.section = outer_section;
+                        result?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn section(&mut self, template: &Template, section: &Section, depth: usize) -> Result<()> {
+        let name = section.path().to_string();
+        let state = match name.as_str() {
+            "@first" | "@last" | "@index" | "@key" => {
+                let truthy = self
+                    .loops
+                    .last()
+                    .is_some_and(|&(i, count)| match name.as_str() {
+                        "@first" => i == 0,
+                        "@last" => i + 1 == count,
+                        "@index" => i > 0,
+                        _ => true,
+                    });
+                if truthy {
+                    SectionState::Once
+                } else {
+                    SectionState::Off
+                }
+            }
+            _ => self
+                .index
+                .get(&name)
+                .map(|&i| self.states[i])
+                .unwrap_or(SectionState::Off),
+        };
+        let truthy = state != SectionState::Off;
+
+        if section.inverted {
+            if !truthy {
+                self.nodes(template, &section.body, depth)?;
+            }
+            return Ok(());
+        }
+
+        match (section.helper(), section.expression.is_some()) {
+            (Some("if" | "with"), _) if truthy => self.nodes(template, &section.body, depth)?,
+            (Some("unless"), _) if !truthy => self.nodes(template, &section.body, depth)?,
+            (Some("if" | "with" | "unless"), _) => {}
+            (Some("each"), _) | (None, false) => {
+                let count = match state {
+                    SectionState::Off => 0,
+                    SectionState::Once => 1,
+                    SectionState::Repeated => 2,
+                };
+                for i in 0..count {
+                    self.loops.push((i, count));
+                    let result = self.nodes(template, &section.body, depth);
+                    self.loops.pop();
+                    result?;
+                }
+            }
+            _ => {
+                let (line, column) = template.line_col(section.open.start);
+                bail!(
+                    "Unknown block helper `{}` at line {}, column {}",
+                    section.name,
+                    line,
+                    column
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decode combination `k` into one state per subject
+fn decode(mut k: usize, radix: &[usize]) -> Vec<SectionState> {
+    radix
+        .iter()
+        .map(|&r| {
+            let digit = k % r;
+            k /= r;
+            [
+                SectionState::Off,
+                SectionState::Once,
+                SectionState::Repeated,
+            ][digit]
+        })
+        .collect()
+}
+
+fn matches(pattern: &[Option<SectionState>], states: &[SectionState]) -> bool {
+    pattern
+        .iter()
+        .zip(states)
+        .all(|(expected, state)| expected.is_none_or(|expected| expected == *state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze(source: &str, strict_json: bool) -> Analysis {
+        MustacheJson5Renderer::new()
+            .unwrap()
+            .analyze(
+                source,
+                &AnalyzeOptions {
+                    strict_json,
+                    ..AnalyzeOptions::default()
+                },
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_valid_separator_logic() {
+        let analysis = analyze(
+            r#"{"items": [{{#each items}}{"name": "{{name}}"}{{#unless @last}},{{/unless}}{{/each}}]}"#,
+            true,
+        );
+        assert!(analysis.is_complete());
+        assert!(analysis.is_valid(), "{:?}", analysis.findings);
+        assert_eq!(analysis.checked, 3);
+    }
+
+    #[test]
+    fn test_missing_separator_needs_repetition() {
+        let analysis = analyze("[{{#each items}}{{.}}{{/each}}]", false);
+        assert_eq!(analysis.findings.len(), 1);
+        let finding = &analysis.findings[0];
+        assert_eq!(
+            finding.states,
+            vec![("items".to_string(), SectionState::Repeated)]
+        );
+        assert!(finding
+            .to_string()
+            .starts_with("with `items` repeated: unexpected `[` where `,` or `]` was expected produced by `{{.}}`"));
+    }
+
+    #[test]
+    fn test_minimal_states_ignore_unrelated_sections() {
+        let analysis = analyze(
+            r#"{"a": 1, {{#if extra}}"b": 2{{/if}} {{#if flag}}"c": true{{/if}}}"#,
+            false,
+        );
+        let states: Vec<_> = analysis
+            .findings
+            .iter()
+            .map(|finding| finding.states.clone())
+            .collect();
+        assert_eq!(
+            states,
+            vec![vec![
+                ("extra".to_string(), SectionState::Once),
+                ("flag".to_string(), SectionState::Once),
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_always_invalid() {
+        let analysis = analyze("{\"a\": }", false);
+        assert_eq!(analysis.findings.len(), 1);
+        assert!(analysis.findings[0].states.is_empty());
+        assert!(analysis.findings[0]
+            .to_string()
+            .starts_with("in every state:"));
+    }
+
+    #[test]
+    fn test_combinations_are_bounded() {
+        let source: String = (0..12)
+            .map(|i| format!("{{{{#s{}}}}}{{{{/s{}}}}}", i, i))
+            .collect();
+        let analysis = MustacheJson5Renderer::new()
+            .unwrap()
+            .analyze(
+                &format!("[{}]", source),
+                &AnalyzeOptions {
+                    max_combinations: 100,
+                    ..AnalyzeOptions::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(analysis.checked, 100);
+        assert!(!analysis.is_complete());
+    }
+}
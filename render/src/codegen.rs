@@ -0,0 +1,256 @@
+//! Generate typed Rust contexts for templates
+//!
+//! [`generate_contexts`] is meant for build scripts: it writes a module per
+//! template under a directory, each with a `Context` struct inferred from
+//! the names the template uses (see [`crate::infer`]) and an implementation
+//! of [`TemplateContext`] that embeds the template and its partials. Render
+//! calls then only compile with data of the right shape:
+//!
+//! ```ignore
+//! // build.rs
+//! mustache_json5_render::codegen::generate_contexts(
+//!     "templates",
+//!     std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("templates.rs"),
+//! )
+//! .unwrap();
+//!
+//! // src/lib.rs
+//! include!(concat!(env!("OUT_DIR"), "/templates.rs"));
+//! ```
+//!
+//! The generated structs derive `serde::Serialize` and `serde::Deserialize`,
+//! so the crate using them needs `serde` with the `derive` feature.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::infer::{snake_case, Shape};
+use crate::partials::{FileSystemResolver, DEFAULT_EXTENSIONS};
+use crate::renderer::MustacheJson5Renderer;
+
+/// Data for a template known at compile time
+pub trait TemplateContext: Serialize {
+    /// The template source
+    const SOURCE: &'static str;
+    /// Partials the template includes, by name
+    const PARTIALS: &'static [(&'static str, &'static str)] = &[];
+
+    /// Render the template with `self` as the root context
+    fn render(&self) -> Result<String> {
+        let mut renderer = MustacheJson5Renderer::new()?;
+        self.render_with(&mut renderer)
+    }
+
+    /// Render with a configured renderer, e.g. one with another escaper
+    fn render_with(&self, renderer: &mut MustacheJson5Renderer) -> Result<String> {
+        for (name, source) in Self::PARTIALS {
+            renderer.add_partial(name, source)?;
+        }
+        let data = serde_json::to_value(self).context("Failed to serialize context")?;
+        renderer.render(Self::SOURCE, &data)
+    }
+}
+
+/// Rust source declaring a context struct called `name` for the template at
+/// `path`, with partials looked up next to it
+///
+/// With `prefix_nested`, nested structs are named after `name` (`PageUser`
+/// rather than `User`) so several contexts can live in one module.
+pub fn context_code(path: &Path, name: &str, prefix_nested: bool) -> Result<String> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("Failed to read template: {}", path.display()))?;
+    let source = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read template: {}", path.display()))?;
+
+    let mut renderer = MustacheJson5Renderer::new()?;
+    if let Some(dir) = path.parent() {
+        renderer.set_resolver(FileSystemResolver::new([dir]));
+    }
+    let template = renderer
+        .compile(&source)
+        .with_context(|| format!("Failed to parse template: {}", path.display()))?;
+
+    let shape = match renderer.infer_template_shape(&template) {
+        Shape::Unknown => Shape::Object(Default::default()),
+        shape @ Shape::Object(_) => shape,
+        _ => bail!(
+            "{} uses the root context as a value, so it has no fields to generate",
+            path.display()
+        ),
+    };
+    let mut code = if prefix_nested {
+        shape.to_rust_prefixed(name)
+    } else {
+        shape.to_rust(name)
+    };
+
+    // Partials the template includes, directly or through other partials
+    let mut partials: Vec<(String, PathBuf)> = Vec::new();
+    let mut pending: Vec<String> = template
+        .partial_references()
+        .into_iter()
+        .map(|(name, _)| name.to_string())
+        .collect();
+    while let Some(partial) = pending.pop() {
+        if partials.iter().any(|(name, _)| *name == partial) {
+            continue;
+        }
+        let (Some(included), Some(partial_path)) =
+            (renderer.partial(&partial), renderer.partial_path(&partial))
+        else {
+            continue;
+        };
+        pending.extend(
+            included
+                .partial_references()
+                .into_iter()
+                .map(|(name, _)| name.to_string()),
+        );
+        partials.push((partial, partial_path.to_path_buf()));
+    }
+    partials.sort();
+
+    let _ = writeln!(
+        code,
+        "\nimpl ::mustache_json5_render::codegen::TemplateContext for {} {{",
+        name
+    );
+    let _ = writeln!(
+        code,
+        "    const SOURCE: &'static str = include_str!({:?});",
+        path.display().to_string()
+    );
+    if !partials.is_empty() {
+        code.push_str("    const PARTIALS: &'static [(&'static str, &'static str)] = &[\n");
+        for (partial, partial_path) in &partials {
+            let _ = writeln!(
+                code,
+                "        ({:?}, include_str!({:?})),",
+                partial,
+                partial_path.display().to_string()
+            );
+        }
+        code.push_str("    ];\n");
+    }
+    code.push_str("}\n");
+    Ok(code)
+}
+
+/// Write a module per template under `dir` to `out`, for use from a build
+/// script
+///
+/// `templates/emails/welcome.mjson5` becomes `emails_welcome::Context`.
+/// Cargo is told to rerun the build script when `dir` changes.
+pub fn generate_contexts(dir: impl AsRef<Path>, out: impl AsRef<Path>) -> Result<()> {
+    let dir = dir.as_ref();
+    let mut templates = Vec::new();
+    find_templates(dir, &mut templates)?;
+    templates.sort();
+
+    let mut code = String::from("// Generated by mustache_json5_render::codegen; do not edit.\n");
+    for template in &templates {
+        let relative = template.strip_prefix(dir).unwrap_or(template);
+        let module = snake_case(
+            &relative
+                .with_extension("")
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("_"),
+        );
+
+        let _ = writeln!(code, "\npub mod {} {{", module);
+        for line in context_code(template, "Context", false)?.lines() {
+            if line.is_empty() {
+                code.push('\n');
+            } else {
+                let _ = writeln!(code, "    {}", line);
+            }
+        }
+        code.push_str("}\n");
+    }
+
+    fs::write(out.as_ref(), code)
+        .with_context(|| format!("Failed to write {}", out.as_ref().display()))?;
+    println!("cargo:rerun-if-changed={}", dir.display());
+    Ok(())
+}
+
+fn find_templates(dir: &Path, templates: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_templates(&path, templates)?;
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| DEFAULT_EXTENSIONS.contains(&ext))
+        {
+            templates.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_context_code_embeds_template_and_partials() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().canonicalize().unwrap();
+        fs::write(
+            dir.join("page.mjson5"),
+            r#"{"title": "{{title}}", {{> footer}}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("footer.mjson5"), r#""year": {{year}}"#).unwrap();
+
+        let code = context_code(&dir.join("page.mjson5"), "Page", true).unwrap();
+        assert!(code.contains(
+            "pub struct Page {\n    pub title: serde_json::Value,\n    pub year: serde_json::Value,\n}"
+        ));
+        assert!(code.contains(&format!(
+            "const SOURCE: &'static str = include_str!({:?});",
+            dir.join("page.mjson5").display().to_string()
+        )));
+        assert!(code.contains(&format!(
+            "(\"footer\", include_str!({:?})),",
+            dir.join("footer.mjson5").display().to_string()
+        )));
+
+        fs::write(dir.join("bare.mjson5"), "{{.}}").unwrap();
+        assert!(context_code(&dir.join("bare.mjson5"), "Bare", true).is_err());
+    }
+
+    #[test]
+    fn test_generate_contexts_writes_a_module_per_template() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("emails")).unwrap();
+        fs::write(
+            dir.join("emails/welcome.mjson5"),
+            r#"{"to": "{{user.email}}"}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("empty.mustache_json5"), "{}").unwrap();
+        fs::write(dir.join("notes.txt"), "{{ignored}}").unwrap();
+
+        let out = dir.join("templates.rs");
+        generate_contexts(dir, &out).unwrap();
+        let code = fs::read_to_string(out).unwrap();
+
+        assert!(code.contains("pub mod emails_welcome {\n    #[derive"));
+        assert!(code.contains("    pub struct Context {\n        pub user: User,\n    }"));
+        assert!(code.contains("pub mod empty {\n    #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]\n    pub struct Context {\n    }"));
+        assert!(!code.contains("ignored"));
+    }
+}
@@ -0,0 +1,139 @@
+//! Context stack used to resolve names while rendering
+//!
+//! Names follow the mustache spec: the first segment of a path is looked up
+//! from the innermost frame outwards, the remaining segments walk into the
+//! value that was found.
+
+use serde_json::Value;
+use std::borrow::Cow;
+
+use crate::template::Path;
+
+/// One level of the context stack
+#[derive(Debug, Clone)]
+enum Frame<'a> {
+    /// A value pushed by a section or helper
+    Value(Cow<'a, Value>),
+    /// Block parameters from `as |item index|`
+    Params(Vec<(String, Value)>),
+}
+
+/// Stack of values visible to the template
+#[derive(Debug, Clone)]
+pub struct Context<'a> {
+    frames: Vec<Frame<'a>>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(root: &'a Value) -> Self {
+        Self {
+            frames: vec![Frame::Value(Cow::Borrowed(root))],
+        }
+    }
+
+    pub fn push(&mut self, value: Cow<'a, Value>) {
+        self.frames.push(Frame::Value(value));
+    }
+
+    pub fn push_params(&mut self, params: Vec<(String, Value)>) {
+        self.frames.push(Frame::Params(params));
+    }
+
+    pub fn pop(&mut self) {
+        if self.frames.len() > 1 {
+            self.frames.pop();
+        }
+    }
+
+    /// Resolve `path`, returning `None` when any segment is missing
+    pub fn lookup(&self, path: &Path) -> Option<&Value> {
+        let Some((first, rest)) = path.0.split_first() else {
+            return self.current();
+        };
+
+        let found = self.frames.iter().rev().find_map(|frame| match frame {
+            Frame::Value(value) => value.get(first.as_str()),
+            Frame::Params(params) => params
+                .iter()
+                .find(|(name, _)| name == first)
+                .map(|(_, value)| value),
+        })?;
+
+        rest.iter()
+            .try_fold(found, |value, segment| child(value, segment))
+    }
+
+    /// The innermost value frame, i.e. `.`
+    pub fn current(&self) -> Option<&Value> {
+        self.frames.iter().rev().find_map(|frame| match frame {
+            Frame::Value(value) => Some(value.as_ref()),
+            Frame::Params(_) => None,
+        })
+    }
+}
+
+fn child<'v>(value: &'v Value, segment: &str) -> Option<&'v Value> {
+    match value {
+        Value::Object(map) => map.get(segment),
+        Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    }
+}
+
+/// Mustache truthiness: `false`, `null`, `0`, `""` and `[]` are falsy
+pub fn is_truthy(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) => false,
+        Some(Value::Bool(b)) => *b,
+        Some(Value::Number(n)) => n.as_f64() != Some(0.0),
+        Some(Value::String(s)) => !s.is_empty(),
+        Some(Value::Array(items)) => !items.is_empty(),
+        Some(Value::Object(_)) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_lookup_walks_stack_and_paths() {
+        let root = json!({"user": {"name": "Ann"}, "title": "T", "items": [1, 2]});
+        let mut context = Context::new(&root);
+        context.push(Cow::Owned(json!({"name": "inner"})));
+
+        assert_eq!(context.lookup(&Path::parse("name")), Some(&json!("inner")));
+        assert_eq!(context.lookup(&Path::parse("title")), Some(&json!("T")));
+        assert_eq!(
+            context.lookup(&Path::parse("user.name")),
+            Some(&json!("Ann"))
+        );
+        assert_eq!(context.lookup(&Path::parse("items.1")), Some(&json!(2)));
+        assert_eq!(context.lookup(&Path::parse("user.missing")), None);
+        assert_eq!(
+            context.lookup(&Path::parse(".")),
+            Some(&json!({"name": "inner"}))
+        );
+    }
+
+    #[test]
+    fn test_params_shadow_values() {
+        let root = json!({"item": "outer"});
+        let mut context = Context::new(&root);
+        context.push_params(vec![("item".to_string(), json!("param"))]);
+
+        assert_eq!(context.lookup(&Path::parse("item")), Some(&json!("param")));
+        assert_eq!(context.lookup(&Path::parse(".")), Some(&root));
+    }
+
+    #[test]
+    fn test_truthiness() {
+        assert!(!is_truthy(None));
+        assert!(!is_truthy(Some(&json!(0))));
+        assert!(!is_truthy(Some(&json!(""))));
+        assert!(!is_truthy(Some(&json!([]))));
+        assert!(is_truthy(Some(&json!({}))));
+        assert!(is_truthy(Some(&json!("x"))));
+    }
+}
@@ -0,0 +1,166 @@
+//! Escaping strategies for interpolated values
+//!
+//! Standard mustache HTML-escapes `{{name}}`, which is wrong for JSON5 output:
+//! a value containing `"` or a newline breaks the document. The default
+//! [`EscapeMode::Json`] escapes according to where the tag appears instead.
+
+use serde_json::Value;
+use std::fmt::Write;
+
+use crate::renderer::stringify;
+use crate::template::Placement;
+
+/// Writes an interpolated value into the output
+///
+/// Only `{{name}}` goes through the escaper; `{{{name}}}` and `{{&name}}`
+/// always emit the raw text.
+pub trait Escaper {
+    fn escape(&self, value: Option<&Value>, placement: Placement, output: &mut String);
+}
+
+/// Built-in escaping strategies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EscapeMode {
+    /// JSON string escaping inside strings, quoted strings for member
+    /// names and JSON literals elsewhere
+    #[default]
+    Json,
+    /// Classic mustache HTML escaping
+    Html,
+    /// Emit values as raw text
+    None,
+}
+
+impl std::str::FromStr for EscapeMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(EscapeMode::Json),
+            "html" => Ok(EscapeMode::Html),
+            "none" => Ok(EscapeMode::None),
+            _ => anyhow::bail!("Unknown escape mode `{}`; expected json, html or none", s),
+        }
+    }
+}
+
+impl Escaper for EscapeMode {
+    fn escape(&self, value: Option<&Value>, placement: Placement, output: &mut String) {
+        match (self, placement) {
+            (EscapeMode::Json, Placement::String { quote }) => {
+                escape_json_string(&stringify(value), quote, output)
+            }
+            (EscapeMode::Json, Placement::Key) => {
+                output.push('"');
+                escape_json_string(&stringify(value), '"', output);
+                output.push('"');
+            }
+            (EscapeMode::Json, Placement::Value) => match value {
+                Some(value) => output.push_str(&value.to_string()),
+                None => output.push_str("null"),
+            },
+            (EscapeMode::Html, _) => escape_html(&stringify(value), output),
+            (EscapeMode::None, _) => output.push_str(&stringify(value)),
+        }
+    }
+}
+
+/// Escape `text` for use between `quote` characters in a JSON5 string
+pub fn escape_json_string(text: &str, quote: char, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            '\u{8}' => output.push_str("\\b"),
+            '\u{c}' => output.push_str("\\f"),
+            c if c == quote => {
+                output.push('\\');
+                output.push(c);
+            }
+            c if c.is_control() => {
+                let _ = write!(output, "\\u{:04x}", c as u32);
+            }
+            c => output.push(c),
+        }
+    }
+}
+
+pub fn escape_html(text: &str, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            c => output.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn escape(mode: EscapeMode, value: Option<&Value>, placement: Placement) -> String {
+        let mut output = String::new();
+        mode.escape(value, placement, &mut output);
+        output
+    }
+
+    #[test]
+    fn test_json_escaping_in_strings() {
+        let double = Placement::String { quote: '"' };
+        let single = Placement::String { quote: '\'' };
+        let value = json!("say \"hi\"\nit's \\");
+
+        assert_eq!(
+            escape(EscapeMode::Json, Some(&value), double),
+            r#"say \"hi\"\nit's \\"#
+        );
+        assert_eq!(
+            escape(EscapeMode::Json, Some(&value), single),
+            r#"say "hi"\nit\'s \\"#
+        );
+        assert_eq!(escape(EscapeMode::Json, Some(&json!(1.5)), double), "1.5");
+        assert_eq!(escape(EscapeMode::Json, None, double), "");
+    }
+
+    #[test]
+    fn test_json_literals_in_value_position() {
+        let value = Placement::Value;
+        assert_eq!(
+            escape(EscapeMode::Json, Some(&json!("a\"b")), value),
+            r#""a\"b""#
+        );
+        assert_eq!(
+            escape(EscapeMode::Json, Some(&json!(false)), value),
+            "false"
+        );
+        assert_eq!(
+            escape(EscapeMode::Json, Some(&json!({"a": [1]})), value),
+            r#"{"a":[1]}"#
+        );
+        assert_eq!(escape(EscapeMode::Json, None, value), "null");
+        assert_eq!(
+            escape(EscapeMode::Json, Some(&json!(7)), Placement::Key),
+            r#""7""#
+        );
+    }
+
+    #[test]
+    fn test_html_and_none() {
+        let value = json!("<a href=\"x\">");
+        assert_eq!(
+            escape(EscapeMode::Html, Some(&value), Placement::Value),
+            "&lt;a href=&quot;x&quot;&gt;"
+        );
+        assert_eq!(
+            escape(EscapeMode::None, Some(&value), Placement::Value),
+            "<a href=\"x\">"
+        );
+        assert!("xml".parse::<EscapeMode>().is_err());
+    }
+}
@@ -0,0 +1,644 @@
+//! Infer the shape of the data a template expects
+//!
+//! Names are resolved the way [`crate::schema`] checks them, but instead of
+//! looking them up the walk records them: `{{user.name}}` makes `user` an
+//! object, sections make arrays of whatever their body uses, and inverted
+//! sections and `if`/`unless` make booleans unless something else says more.
+//! A name that has not been seen yet belongs to the innermost section.
+//!
+//! The resulting [`Shape`] can be written as JSON Schema, a TypeScript
+//! interface or Rust structs.
+
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::renderer::{MustacheJson5Renderer, MAX_PARTIAL_DEPTH};
+use crate::template::{Node, Path, Placement, Section, Template};
+
+/// The inferred type of a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Shape {
+    /// Nothing is known about the value
+    Unknown,
+    /// Interpolated as a JSON value, so any value will do
+    Any,
+    /// Interpolated into a string or member name
+    Scalar,
+    /// Only tested for truthiness
+    Boolean,
+    Object(BTreeMap<String, Field>),
+    Array(Box<Shape>),
+}
+
+/// A property of an object shape
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub shape: Shape,
+    /// Only tested by a condition, so the template works without it
+    pub optional: bool,
+}
+
+impl MustacheJson5Renderer {
+    /// Infer the data shape `source` and its partials expect
+    pub fn infer_shape(&mut self, source: &str) -> Result<Shape> {
+        let template = self.compile(source)?;
+        Ok(self.infer_template_shape(&template))
+    }
+
+    /// Infer the shape for a compiled template; only loaded partials are followed
+    pub fn infer_template_shape(&self, template: &Template) -> Shape {
+        let mut inferrer = Inferrer {
+            renderer: self,
+            root: Shape::Unknown,
+            scopes: vec![Scope::Value(Vec::new())],
+            partials: Vec::new(),
+        };
+        inferrer.nodes(template.nodes());
+        inferrer.root
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Property(String),
+    Items,
+}
+
+enum Scope {
+    Value(Vec<Step>),
+    /// Block parameters; `None` for indexes and keys, which need no data
+    Params(Vec<(String, Option<Vec<Step>>)>),
+}
+
+struct Inferrer<'a> {
+    renderer: &'a MustacheJson5Renderer,
+    root: Shape,
+    scopes: Vec<Scope>,
+    partials: Vec<String>,
+}
+
+impl Inferrer<'_> {
+    fn nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Variable(variable) => {
+                    let leaf = match variable.placement {
+                        Placement::Value => Shape::Any,
+                        Placement::Key | Placement::String { .. } => Shape::Scalar,
+                    };
+                    if let Some(steps) = self.resolve(&variable.path) {
+                        self.touch(&steps, leaf, true);
+                    }
+                }
+                Node::Section(section) => self.section(section),
+                Node::Partial(partial) => {
+                    if self.partials.len() >= MAX_PARTIAL_DEPTH
+                        || self.partials.contains(&partial.name)
+                    {
+                        continue;
+                    }
+                    if let Some(included) = self.renderer.partial(&partial.name) {
+                        self.partials.push(partial.name.clone());
+                        self.nodes(included.nodes());
+                        self.partials.pop();
+                    }
+                }
+                Node::Text(_) | Node::Comment(_) => {}
+            }
+        }
+    }
+
+    fn section(&mut self, section: &Section) {
+        let Some(steps) = self.resolve(&section.path()) else {
+            self.nodes(&section.body);
+            return;
+        };
+        let mut item = steps.clone();
+        item.push(Step::Items);
+
+        match (section.inverted, section.helper()) {
+            (true, _) | (false, Some("if" | "unless")) => {
+                self.touch(&steps, Shape::Boolean, false);
+                self.nodes(&section.body);
+            }
+            (false, Some("with")) => {
+                self.touch(&steps, Shape::Unknown, true);
+                self.scoped(section, steps.clone(), vec![Some(steps)]);
+            }
+            (false, Some("each")) => {
+                self.touch(&item, Shape::Unknown, true);
+                self.scoped(section, item.clone(), vec![Some(item), None]);
+            }
+            (false, _) => {
+                self.scoped(section, item, Vec::new());
+                // A section whose body uses nothing is a condition
+                match get(&self.root, &steps) {
+                    None | Some(Shape::Unknown) => self.touch(&steps, Shape::Boolean, false),
+                    Some(_) => self.touch(&steps, Shape::Unknown, true),
+                }
+            }
+        }
+    }
+
+    fn scoped(&mut self, section: &Section, value: Vec<Step>, params: Vec<Option<Vec<Step>>>) {
+        self.scopes.push(Scope::Value(value));
+        self.scopes.push(Scope::Params(
+            section.parameters.iter().cloned().zip(params).collect(),
+        ));
+        self.nodes(&section.body);
+        self.scopes.truncate(self.scopes.len() - 2);
+    }
+
+    /// Where `path` points in the root shape, or `None` if it needs no data
+    fn resolve(&self, path: &Path) -> Option<Vec<Step>> {
+        let innermost = self.scopes.iter().rev().find_map(|scope| match scope {
+            Scope::Value(steps) => Some(steps),
+            Scope::Params(_) => None,
+        })?;
+        let Some((first, rest)) = path.0.split_first() else {
+            return Some(innermost.clone());
+        };
+        if first.starts_with('@') {
+            return None;
+        }
+
+        let mut steps = None;
+        for scope in self.scopes.iter().rev() {
+            match scope {
+                Scope::Params(params) => {
+                    if let Some((_, param)) = params.iter().find(|(name, _)| name == first) {
+                        steps = Some(param.clone()?);
+                        break;
+                    }
+                }
+                Scope::Value(value) => {
+                    if let Some(Shape::Object(fields)) = get(&self.root, value) {
+                        if fields.contains_key(first) {
+                            let mut value = value.clone();
+                            value.push(Step::Property(first.clone()));
+                            steps = Some(value);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        let mut steps = steps.unwrap_or_else(|| {
+            let mut steps = innermost.clone();
+            steps.push(Step::Property(first.clone()));
+            steps
+        });
+
+        for segment in rest {
+            steps.push(match segment.parse::<usize>() {
+                Ok(_) => Step::Items,
+                Err(_) => Step::Property(segment.clone()),
+            });
+        }
+        Some(steps)
+    }
+
+    /// Record a use of the value at `steps`
+    fn touch(&mut self, steps: &[Step], leaf: Shape, required: bool) {
+        let mut shape = &mut self.root;
+        for step in steps {
+            shape = match step {
+                Step::Property(name) => {
+                    if let Shape::Array(items) = shape {
+                        shape = items.as_mut();
+                    }
+                    if !matches!(shape, Shape::Object(_)) {
+                        *shape = Shape::Object(BTreeMap::new());
+                    }
+                    let Shape::Object(fields) = shape else {
+                        unreachable!()
+                    };
+                    let field = fields.entry(name.clone()).or_insert(Field {
+                        shape: Shape::Unknown,
+                        optional: !required,
+                    });
+                    field.optional &= !required;
+                    &mut field.shape
+                }
+                Step::Items => {
+                    if !matches!(shape, Shape::Array(_)) {
+                        let item = match std::mem::replace(shape, Shape::Unknown) {
+                            object @ Shape::Object(_) => object,
+                            _ => Shape::Unknown,
+                        };
+                        *shape = Shape::Array(Box::new(item));
+                    }
+                    let Shape::Array(items) = shape else {
+                        unreachable!()
+                    };
+                    items.as_mut()
+                }
+            };
+        }
+        if leaf.rank() > shape.rank() {
+            *shape = leaf;
+        }
+    }
+}
+
+fn get<'a>(mut shape: &'a Shape, steps: &[Step]) -> Option<&'a Shape> {
+    for step in steps {
+        shape = match (step, shape) {
+            (Step::Property(name), Shape::Object(fields)) => &fields.get(name)?.shape,
+            (Step::Property(name), Shape::Array(items)) => match items.as_ref() {
+                Shape::Object(fields) => &fields.get(name)?.shape,
+                _ => return None,
+            },
+            (Step::Items, Shape::Array(items)) => items,
+            _ => return None,
+        };
+    }
+    Some(shape)
+}
+
+impl Shape {
+    /// How much a use says about the value; more specific uses win
+    fn rank(&self) -> u8 {
+        match self {
+            Shape::Unknown => 0,
+            Shape::Any => 1,
+            Shape::Boolean => 2,
+            Shape::Scalar => 3,
+            Shape::Object(_) | Shape::Array(_) => 4,
+        }
+    }
+
+    /// Write the shape as a JSON Schema document
+    pub fn to_json_schema(&self) -> Value {
+        let mut schema = json!({"$schema": "https://json-schema.org/draft/2020-12/schema"});
+        if let Value::Object(body) = self.json_schema() {
+            schema.as_object_mut().expect("object").extend(body);
+        }
+        schema
+    }
+
+    fn json_schema(&self) -> Value {
+        match self {
+            Shape::Unknown | Shape::Any => json!({}),
+            Shape::Scalar => json!({"type": ["string", "number", "boolean"]}),
+            Shape::Boolean => json!({"type": "boolean"}),
+            Shape::Array(items) => json!({"type": "array", "items": items.json_schema()}),
+            Shape::Object(fields) => {
+                let properties: Map<String, Value> = fields
+                    .iter()
+                    .map(|(name, field)| (name.clone(), field.shape.json_schema()))
+                    .collect();
+                let required: Vec<&String> = fields
+                    .iter()
+                    .filter(|(_, field)| !field.optional)
+                    .map(|(name, _)| name)
+                    .collect();
+                let mut schema = json!({"type": "object", "properties": properties});
+                if !required.is_empty() {
+                    schema["required"] = json!(required);
+                }
+                schema
+            }
+        }
+    }
+
+    /// Write the shape as a TypeScript declaration called `name`
+    pub fn to_typescript(&self, name: &str) -> String {
+        match self {
+            Shape::Object(_) => format!("export interface {} {}\n", name, self.typescript(0)),
+            _ => format!("export type {} = {};\n", name, self.typescript(0)),
+        }
+    }
+
+    fn typescript(&self, depth: usize) -> String {
+        match self {
+            Shape::Unknown | Shape::Any => "unknown".to_string(),
+            Shape::Scalar => "string | number | boolean".to_string(),
+            Shape::Boolean => "boolean".to_string(),
+            Shape::Array(items) => match items.as_ref() {
+                Shape::Scalar => format!("({})[]", items.typescript(depth)),
+                items => format!("{}[]", items.typescript(depth)),
+            },
+            Shape::Object(fields) => {
+                let indent = "  ".repeat(depth + 1);
+                let mut out = String::from("{\n");
+                for (name, field) in fields {
+                    let key = if is_identifier(name) {
+                        name.clone()
+                    } else {
+                        Value::from(name.as_str()).to_string()
+                    };
+                    let _ = writeln!(
+                        out,
+                        "{}{}{}: {};",
+                        indent,
+                        key,
+                        if field.optional { "?" } else { "" },
+                        field.shape.typescript(depth + 1)
+                    );
+                }
+                out.push_str(&"  ".repeat(depth));
+                out.push('}');
+                out
+            }
+        }
+    }
+
+    /// Write the shape as serializable Rust structs, the root called `name`
+    pub fn to_rust(&self, name: &str) -> String {
+        self.rust(name, "")
+    }
+
+    /// Like [`Shape::to_rust`], with nested struct names prefixed by `name`
+    /// so several contexts can share a module
+    pub fn to_rust_prefixed(&self, name: &str) -> String {
+        self.rust(name, name)
+    }
+
+    fn rust(&self, name: &str, prefix: &str) -> String {
+        let mut structs = RustStructs {
+            prefix: prefix.to_string(),
+            ..RustStructs::default()
+        };
+        match self {
+            Shape::Object(fields) => {
+                structs.names.push(name.to_string());
+                structs.add(name, fields);
+            }
+            _ => {
+                let alias = format!("pub type {} = {};\n", name, structs.type_of(name, "", self));
+                structs.structs.insert(0, alias);
+            }
+        }
+        structs.structs.join("\n")
+    }
+}
+
+/// Rust structs written so far, in the order they were first referenced
+#[derive(Default)]
+struct RustStructs {
+    /// Prepended to the names of nested structs
+    prefix: String,
+    names: Vec<String>,
+    structs: Vec<String>,
+}
+
+impl RustStructs {
+    fn add(&mut self, name: &str, fields: &BTreeMap<String, Field>) {
+        let slot = self.structs.len();
+        self.structs.push(String::new());
+
+        let mut body = String::new();
+        let mut idents: Vec<String> = Vec::new();
+        for (key, field) in fields {
+            // Keys like `userId` and `user_id` map to the same field name
+            let mut ident = snake_case(key);
+            while idents.contains(&ident) {
+                ident.push('_');
+            }
+            idents.push(ident.clone());
+            let mut ty = self.type_of(name, key, &field.shape);
+            if *key != ident.trim_start_matches("r#") {
+                let _ = writeln!(body, "    #[serde(rename = {})]", Value::from(key.as_str()));
+            }
+            if field.optional {
+                body.push_str("    #[serde(skip_serializing_if = \"Option::is_none\")]\n");
+                ty = format!("Option<{}>", ty);
+            }
+            let _ = writeln!(body, "    pub {}: {},", ident, ty);
+        }
+        self.structs[slot] = format!(
+            "#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]\npub struct {} {{\n{}}}\n",
+            name, body
+        );
+    }
+
+    fn type_of(&mut self, parent: &str, key: &str, shape: &Shape) -> String {
+        match shape {
+            // Scalars may be strings, numbers or booleans, as in the other outputs
+            Shape::Unknown | Shape::Any | Shape::Scalar => "serde_json::Value".to_string(),
+            Shape::Boolean => "bool".to_string(),
+            Shape::Array(items) => format!("Vec<{}>", self.type_of(parent, &singular(key), items)),
+            Shape::Object(fields) => {
+                let mut name = format!("{}{}", self.prefix, pascal_case(key));
+                if name == self.prefix || self.names.contains(&name) {
+                    name = format!("{}{}", parent, pascal_case(key));
+                }
+                while self.names.contains(&name) {
+                    name.push('_');
+                }
+                self.names.push(name.clone());
+                self.add(&name, fields);
+                name
+            }
+        }
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+fn singular(name: &str) -> String {
+    match name.strip_suffix("ies") {
+        Some(stem) if !stem.is_empty() => format!("{}y", stem),
+        _ => match name.strip_suffix('s') {
+            Some(stem) if !stem.is_empty() && !stem.ends_with('s') => stem.to_string(),
+            _ => format!("{}Item", name),
+        },
+    }
+}
+
+fn pascal_case(name: &str) -> String {
+    let mut out = String::new();
+    for part in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            out.push(first.to_ascii_uppercase());
+            out.extend(chars);
+        }
+    }
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, 'T');
+    }
+    if out == "Self" {
+        out.push('_');
+    }
+    out
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "yield", "gen",
+];
+
+/// Keywords that cannot be raw identifiers
+const PATH_KEYWORDS: &[&str] = &["crate", "self", "super"];
+
+pub(crate) fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if previous_lower {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+            previous_lower = false;
+        } else if c.is_ascii_alphanumeric() {
+            out.push(c);
+            previous_lower = true;
+        } else if !out.ends_with('_') {
+            out.push('_');
+            previous_lower = false;
+        }
+    }
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    if PATH_KEYWORDS.contains(&out.as_str()) {
+        out.push('_');
+    } else if RUST_KEYWORDS.contains(&out.as_str()) {
+        out.insert_str(0, "r#");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infer(source: &str) -> Shape {
+        MustacheJson5Renderer::new()
+            .unwrap()
+            .infer_shape(source)
+            .unwrap()
+    }
+
+    const TEMPLATE: &str = r#"{
+  "name": "{{user.name}}",
+  "id": {{user.id}},
+  "tags": [{{#each user.tags as |tag|}}"{{tag}}",{{/each}}],
+  "items": [{{#items}}{"id": {{id}}, "label": "{{label}}"},{{/items}}],
+  {{^draft}}"published": true,{{/draft}}
+  "title": "{{title}}"
+}"#;
+
+    #[test]
+    fn test_infer_json_schema() {
+        let schema = infer(TEMPLATE).to_json_schema();
+        assert_eq!(
+            schema,
+            json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "type": "object",
+                "properties": {
+                    "draft": {"type": "boolean"},
+                    "items": {"type": "array", "items": {
+                        "type": "object",
+                        "properties": {"id": {}, "label": {"type": ["string", "number", "boolean"]}},
+                        "required": ["id", "label"]
+                    }},
+                    "title": {"type": ["string", "number", "boolean"]},
+                    "user": {
+                        "type": "object",
+                        "properties": {
+                            "id": {},
+                            "name": {"type": ["string", "number", "boolean"]},
+                            "tags": {"type": "array", "items": {"type": ["string", "number", "boolean"]}}
+                        },
+                        "required": ["id", "name", "tags"]
+                    }
+                },
+                "required": ["items", "title", "user"]
+            })
+        );
+
+        // The inferred schema accepts the template it came from
+        let issues = MustacheJson5Renderer::new()
+            .unwrap()
+            .check_schema(TEMPLATE, &schema)
+            .unwrap();
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_outer_names_stay_outside_sections() {
+        let shape = infer("{{title}}{{#items}}{{title}}\"{{name}}\"{{/items}}{{#flag}}{{/flag}}");
+        let Shape::Object(fields) = shape else {
+            panic!("expected an object")
+        };
+        assert_eq!(fields["flag"].shape, Shape::Boolean);
+        assert!(fields["flag"].optional);
+        assert_eq!(
+            fields["items"].shape.to_typescript("Items"),
+            "export type Items = {\n  name: string | number | boolean;\n}[];\n"
+        );
+    }
+
+    #[test]
+    fn test_infer_typescript() {
+        assert_eq!(
+            infer(r#"{"a": "{{user.first-name}}", {{#if ok}}"b": {{value}}{{/if}}}"#)
+                .to_typescript("Context"),
+            "export interface Context {\n  ok?: boolean;\n  user: {\n    \"first-name\": string | number | boolean;\n  };\n  value: unknown;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_infer_rust() {
+        let rust = infer(r#"{"user": "{{user.firstName}}", "type": {{type}}, "rows": [{{#each entries}}"{{name}}"{{/each}}]}"#)
+            .to_rust("Context");
+        assert_eq!(
+            rust,
+            r#"#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Context {
+    pub entries: Vec<Entry>,
+    pub r#type: serde_json::Value,
+    pub user: User,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    pub name: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct User {
+    #[serde(rename = "firstName")]
+    pub first_name: serde_json::Value,
+}
+"#
+        );
+
+        let prefixed =
+            infer("{{user.name}}{{#each user.roles}}{{id}}{{/each}}").to_rust_prefixed("Page");
+        assert!(prefixed.contains("pub struct Page {\n    pub user: PageUser,\n}"));
+        assert!(prefixed.contains("pub roles: Vec<PageRole>,"));
+
+        let keywords = infer(r#"["{{self.id}}", "{{super}}", "{{crate}}"]"#).to_rust("Context");
+        assert!(keywords.contains("#[serde(rename = \"self\")]\n    pub self_: Self_,"));
+        assert!(
+            keywords.contains("#[serde(rename = \"super\")]\n    pub super_: serde_json::Value,")
+        );
+        assert!(
+            keywords.contains("#[serde(rename = \"crate\")]\n    pub crate_: serde_json::Value,")
+        );
+        assert!(keywords.contains("pub struct Self_ {"));
+
+        let clashing = infer(r#"["{{userId}}", "{{user_id}}"]"#).to_rust("Context");
+        assert!(
+            clashing.contains("#[serde(rename = \"userId\")]\n    pub user_id: serde_json::Value,")
+        );
+        assert!(clashing
+            .contains("#[serde(rename = \"user_id\")]\n    pub user_id_: serde_json::Value,"));
+    }
+}
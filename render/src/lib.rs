@@ -0,0 +1,123 @@
+//! Render Mustache JSON5 templates
+//!
+//! Templates are parsed with the tree-sitter grammar and rendered against a
+//! JSON context. Sections, inverted sections, partials and the `each`, `if`,
+//! `unless` and `with` block helpers are supported.
+//!
+//! By default `{{name}}` is escaped for JSON: inside a string the value is
+//! string-escaped, elsewhere it is written as a JSON literal. See [`escape`]
+//! for the other strategies.
+//!
+//! ```
+//! use serde_json::json;
+//!
+//! let output = mustache_json5_render::render(
+//!     r#"{"name": "{{name}}", "tags": [{{#tags}}"{{.}}", {{/tags}}]}"#,
+//!     &json!({"name": "demo", "tags": ["a", "b"]}),
+//! )
+//! .unwrap();
+//! assert_eq!(output, r#"{"name": "demo", "tags": ["a", "b", ]}"#);
+//! ```
+
+pub mod analyze;
+pub mod codegen;
+pub mod context;
+pub mod escape;
+pub mod infer;
+pub mod partials;
+pub mod renderer;
+pub mod schema;
+pub mod template;
+pub mod validate;
+
+pub use analyze::{Analysis, AnalyzeOptions, Finding, SectionState};
+pub use codegen::TemplateContext;
+pub use escape::{EscapeMode, Escaper};
+pub use infer::{Field, Shape};
+pub use partials::{FileSystemResolver, MemoryResolver, PartialResolver};
+pub use renderer::{MustacheJson5Renderer, Rendered, Segment};
+pub use schema::SchemaIssue;
+pub use template::Template;
+pub use validate::{ValidateOptions, ValidationError};
+
+use serde_json::Value;
+
+pub type Result<T> = anyhow::Result<T>;
+
+/// Render `source` with `data` as the root context and no partials
+pub fn render(source: &str, data: &Value) -> Result<String> {
+    MustacheJson5Renderer::new()?.render(source, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_values_and_strings() {
+        let output = render(
+            r#"{"count": {{count}}, "greeting": "Hello {{user.name}}"}"#,
+            &json!({"count": 3, "user": {"name": "Ann"}}),
+        )
+        .unwrap();
+        assert_eq!(output, r#"{"count": 3, "greeting": "Hello Ann"}"#);
+    }
+
+    #[test]
+    fn test_render_sections() {
+        let data = json!({"items": [1, 2], "empty": [], "flag": true});
+        assert_eq!(
+            render("[{{#items}}{{.}},{{/items}}]", &data).unwrap(),
+            "[1,2,]"
+        );
+        assert_eq!(render("{{^empty}}none{{/empty}}", &data).unwrap(), "none");
+        assert_eq!(render("{{#flag}}on{{/flag}}", &data).unwrap(), "on");
+        assert_eq!(render("{{#missing}}x{{/missing}}", &data).unwrap(), "");
+    }
+
+    #[test]
+    fn test_render_helpers_with_parameters() {
+        let data = json!({"users": [{"name": "a"}, {"name": "b"}], "ok": false});
+        let output = render(
+            "[{{#each users as |user i|}}\"{{i}}:{{user.name}}\",{{/each}}]",
+            &data,
+        )
+        .unwrap();
+        assert_eq!(output, r#"["0:a","1:b",]"#);
+        assert_eq!(render("{{#unless ok}}no{{/unless}}", &data).unwrap(), "no");
+    }
+
+    #[test]
+    fn test_render_json_escaping() {
+        let data = json!({"text": "a \"quoted\"\nline", "list": [1, "x"], "n": null});
+        let output = render(
+            r#"{"s": "<{{text}}>", "v": {{text}}, "l": {{list}}, "n": {{n}}, "m": {{missing}}}"#,
+            &data,
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            r#"{"s": "<a \"quoted\"\nline>", "v": "a \"quoted\"\nline", "l": [1,"x"], "n": null, "m": null}"#
+        );
+        serde_json::from_str::<Value>(&output).unwrap();
+
+        // Triple mustaches bypass escaping
+        assert_eq!(render("{{{text}}}", &data).unwrap(), "a \"quoted\"\nline");
+    }
+
+    #[test]
+    fn test_render_html_escaping() {
+        let mut renderer = MustacheJson5Renderer::new().unwrap();
+        renderer.set_escaper(EscapeMode::Html);
+        let data = json!({"html": "<b>"});
+        assert_eq!(renderer.render("{{html}}", &data).unwrap(), "&lt;b&gt;");
+        assert_eq!(renderer.render("{{{html}}}", &data).unwrap(), "<b>");
+    }
+
+    #[test]
+    fn test_unknown_helper_is_an_error() {
+        let error = render("{{#repeat items}}x{{/repeat}}", &json!({})).unwrap_err();
+        assert!(error.to_string().contains("Unknown block helper `repeat`"));
+    }
+}
@@ -0,0 +1,300 @@
+//! Resolve `{{> name}}` references to partial templates
+//!
+//! The renderer asks a [`PartialResolver`] for each partial name it finds,
+//! transitively, before rendering. [`FileSystemResolver`] looks partials up
+//! under search paths, mapping dotted names like `shared.header` to
+//! directories; [`MemoryResolver`] serves them from a map.
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Extensions tried by [`FileSystemResolver`] by default
+pub const DEFAULT_EXTENSIONS: &[&str] = &["mustache_json5", "mjson5"];
+
+/// The source of a resolved partial
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedPartial {
+    pub source: String,
+    /// File the partial was read from, when there is one
+    pub path: Option<PathBuf>,
+}
+
+/// Looks up partial templates by name
+pub trait PartialResolver {
+    /// Return the partial called `name`, or `None` if there is none
+    fn resolve(&self, name: &str) -> Result<Option<ResolvedPartial>>;
+}
+
+/// Serves partials from memory, mainly for tests and embedding
+#[derive(Debug, Clone, Default)]
+pub struct MemoryResolver {
+    partials: HashMap<String, String>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, source: impl Into<String>) {
+        self.partials.insert(name.into(), source.into());
+    }
+}
+
+impl<N: Into<String>, S: Into<String>> FromIterator<(N, S)> for MemoryResolver {
+    fn from_iter<I: IntoIterator<Item = (N, S)>>(iter: I) -> Self {
+        Self {
+            partials: iter
+                .into_iter()
+                .map(|(name, source)| (name.into(), source.into()))
+                .collect(),
+        }
+    }
+}
+
+impl PartialResolver for MemoryResolver {
+    fn resolve(&self, name: &str) -> Result<Option<ResolvedPartial>> {
+        Ok(self.partials.get(name).map(|source| ResolvedPartial {
+            source: source.clone(),
+            path: None,
+        }))
+    }
+}
+
+/// Reads partials from files under one or more search paths
+///
+/// `{{> shared.header}}` is looked up as `shared/header.<ext>` and then as
+/// `shared.header.<ext>` in each search path in turn, for each extension.
+#[derive(Debug, Clone)]
+pub struct FileSystemResolver {
+    search_paths: Vec<PathBuf>,
+    extensions: Vec<String>,
+    dotted_directories: bool,
+}
+
+impl FileSystemResolver {
+    pub fn new<P: Into<PathBuf>>(search_paths: impl IntoIterator<Item = P>) -> Self {
+        Self {
+            search_paths: search_paths.into_iter().map(Into::into).collect(),
+            extensions: DEFAULT_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+            dotted_directories: true,
+        }
+    }
+
+    /// Extensions to try, without the leading dot; an empty string matches
+    /// files named exactly like the partial
+    pub fn with_extensions<S: Into<String>>(
+        mut self,
+        extensions: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.extensions = extensions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Whether dots in names separate directories (the default)
+    pub fn with_dotted_directories(mut self, enabled: bool) -> Self {
+        self.dotted_directories = enabled;
+        self
+    }
+
+    /// Find the file a partial name refers to
+    pub fn locate(&self, name: &str) -> Option<PathBuf> {
+        let mut stems = Vec::new();
+        if self.dotted_directories && name.contains('.') {
+            stems.push(name.split('.').collect::<PathBuf>());
+        }
+        stems.push(PathBuf::from(name));
+
+        // Names must stay inside the search paths
+        if stems.iter().any(|stem| {
+            stem.components()
+                .any(|component| !matches!(component, Component::Normal(_)))
+        }) {
+            return None;
+        }
+
+        self.search_paths.iter().find_map(|dir| {
+            stems.iter().find_map(|stem| {
+                self.extensions
+                    .iter()
+                    .map(|ext| with_extension(&dir.join(stem), ext))
+                    .find(|path| path.is_file())
+            })
+        })
+    }
+}
+
+fn with_extension(path: &Path, ext: &str) -> PathBuf {
+    if ext.is_empty() {
+        return path.to_path_buf();
+    }
+    let mut file = path.as_os_str().to_owned();
+    file.push(".");
+    file.push(ext);
+    PathBuf::from(file)
+}
+
+impl PartialResolver for FileSystemResolver {
+    fn resolve(&self, name: &str) -> Result<Option<ResolvedPartial>> {
+        let Some(path) = self.locate(name) else {
+            return Ok(None);
+        };
+        let source = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read partial: {}", path.display()))?;
+        Ok(Some(ResolvedPartial {
+            source,
+            path: Some(path),
+        }))
+    }
+}
+
+/// Report partials reachable from `roots` that include themselves without a
+/// section in between
+///
+/// `edges` maps each partial to the partials it includes, flagged `true`
+/// when the reference sits inside a section. Recursion through a section can
+/// end once the data runs out, so only unguarded cycles are errors.
+pub fn check_cycles(edges: &HashMap<String, Vec<(String, bool)>>, roots: &[String]) -> Result<()> {
+    fn visit(
+        name: &str,
+        edges: &HashMap<String, Vec<(String, bool)>>,
+        path: &mut Vec<String>,
+        done: &mut Vec<String>,
+    ) -> Result<()> {
+        if let Some(start) = path.iter().position(|entry| entry == name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(name.to_string());
+            bail!(
+                "Partial `{}` includes itself without an enclosing section: {}",
+                name,
+                cycle.join(" -> ")
+            );
+        }
+        if done.iter().any(|entry| entry == name) {
+            return Ok(());
+        }
+
+        path.push(name.to_string());
+        for (child, guarded) in edges.get(name).into_iter().flatten() {
+            if !guarded {
+                visit(child, edges, path, done)?;
+            }
+        }
+        path.pop();
+        done.push(name.to_string());
+        Ok(())
+    }
+
+    // Every partial the template includes can be rendered, guarded or not
+    let mut reachable: Vec<&String> = Vec::new();
+    let mut pending: Vec<&String> = roots.iter().collect();
+    while let Some(name) = pending.pop() {
+        if !reachable.contains(&name) {
+            reachable.push(name);
+            pending.extend(
+                edges
+                    .get(name)
+                    .into_iter()
+                    .flatten()
+                    .map(|(child, _)| child),
+            );
+        }
+    }
+    reachable.sort();
+
+    let mut done = Vec::new();
+    for name in reachable {
+        visit(name, edges, &mut Vec::new(), &mut done)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_memory_resolver() {
+        let resolver: MemoryResolver = [("a", "1")].into_iter().collect();
+        assert_eq!(resolver.resolve("a").unwrap().unwrap().source, "1");
+        assert!(resolver.resolve("b").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_file_system_resolver() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        let first = dir.join("first");
+        let second = dir.join("second");
+        fs::create_dir_all(first.join("shared")).unwrap();
+        fs::create_dir_all(&second).unwrap();
+        fs::write(first.join("shared/header.mjson5"), "header").unwrap();
+        fs::write(second.join("footer.mustache_json5"), "footer").unwrap();
+        fs::write(second.join("a.b.mjson5"), "literal").unwrap();
+        fs::write(dir.join("secret.mjson5"), "secret").unwrap();
+
+        let resolver = FileSystemResolver::new([&first, &second]);
+        assert_eq!(
+            resolver.locate("shared.header"),
+            Some(first.join("shared/header.mjson5"))
+        );
+        assert_eq!(
+            resolver.resolve("footer").unwrap().unwrap().source,
+            "footer"
+        );
+        assert_eq!(resolver.resolve("a.b").unwrap().unwrap().source, "literal");
+        assert!(resolver.locate("missing").is_none());
+        assert!(resolver.locate("../secret").is_none());
+
+        let resolver = FileSystemResolver::new([&first]).with_dotted_directories(false);
+        assert!(resolver.locate("shared.header").is_none());
+
+        let resolver = FileSystemResolver::new([&second]).with_extensions(["json5"]);
+        assert!(resolver.locate("footer").is_none());
+    }
+
+    #[test]
+    fn test_check_cycles() {
+        let edges = |list: &[(&str, &str, bool)]| {
+            let mut edges: HashMap<String, Vec<(String, bool)>> = HashMap::new();
+            for (from, to, guarded) in list {
+                edges
+                    .entry(from.to_string())
+                    .or_default()
+                    .push((to.to_string(), *guarded));
+            }
+            edges
+        };
+
+        let roots = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let cycle = edges(&[("a", "b", false), ("b", "a", false)]);
+        let error = check_cycles(&cycle, &roots(&["a"])).unwrap_err();
+        assert!(error.to_string().ends_with("a -> b -> a"));
+        // Only partials the template can reach are checked
+        check_cycles(&cycle, &roots(&["c"])).unwrap();
+        check_cycles(&cycle, &[]).unwrap();
+
+        // Reaching a cycle through a section still renders it
+        let guarded = edges(&[("page", "a", true), ("a", "a", false)]);
+        check_cycles(&guarded, &roots(&["page"])).unwrap_err();
+
+        check_cycles(&edges(&[("tree", "tree", true)]), &roots(&["tree"])).unwrap();
+        check_cycles(
+            &edges(&[("a", "b", false), ("a", "c", false), ("b", "c", false)]),
+            &roots(&["a"]),
+        )
+        .unwrap();
+    }
+}
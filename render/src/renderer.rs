@@ -0,0 +1,435 @@
+//! Render compiled templates against a JSON context
+
+use anyhow::{bail, Context as _, Result};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tree_sitter::Parser;
+
+use crate::context::{is_truthy, Context};
+use crate::escape::{EscapeMode, Escaper};
+use crate::partials::{self, PartialResolver};
+use crate::template::{self, Node, Section, Template};
+
+/// Maximum nesting of partials, to stop runaway recursion
+pub(crate) const MAX_PARTIAL_DEPTH: usize = 100;
+
+/// What produced a stretch of rendered output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// Byte range in the rendered output
+    pub output: Range<usize>,
+    /// Partial the text came from, or `None` for the rendered template
+    pub partial: Option<String>,
+    /// Byte range of the text or tag in that template
+    pub source: Range<usize>,
+    /// Whether the output is a variable's value rather than copied text
+    pub variable: bool,
+    /// Opening tag of the innermost section being rendered, if any
+    pub section: Option<Range<usize>>,
+}
+
+/// Rendered output with the template origin of every byte
+#[derive(Debug, Clone, Default)]
+pub struct Rendered {
+    pub output: String,
+    pub segments: Vec<Segment>,
+}
+
+impl Rendered {
+    /// The segment that produced the byte at `offset`
+    ///
+    /// Offsets at the end of the output resolve to the last segment, so
+    /// errors about unexpected end of input still have an origin.
+    pub fn segment_at(&self, offset: usize) -> Option<&Segment> {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.output.end <= offset);
+        self.segments
+            .get(index)
+            .filter(|segment| segment.output.start <= offset)
+            .or_else(|| self.segments.last().filter(|_| offset >= self.output.len()))
+    }
+}
+
+/// Output buffer that optionally records where each piece came from
+struct Output {
+    rendered: Rendered,
+    track: bool,
+    partial: Option<String>,
+    section: Option<Range<usize>>,
+}
+
+impl Output {
+    fn new(capacity: usize, track: bool) -> Self {
+        Self {
+            rendered: Rendered {
+                output: String::with_capacity(capacity),
+                segments: Vec::new(),
+            },
+            track,
+            partial: None,
+            section: None,
+        }
+    }
+
+    fn record(&mut self, start: usize, source: Range<usize>, variable: bool) {
+        let end = self.rendered.output.len();
+        if self.track && end > start {
+            self.rendered.segments.push(Segment {
+                output: start..end,
+                partial: self.partial.clone(),
+                source,
+                variable,
+                section: self.section.clone(),
+            });
+        }
+    }
+}
+
+/// Renders Mustache JSON5 templates
+pub struct MustacheJson5Renderer {
+    parser: Parser,
+    partials: HashMap<String, Template>,
+    partial_paths: HashMap<String, PathBuf>,
+    resolver: Option<Box<dyn PartialResolver>>,
+    escaper: Box<dyn Escaper>,
+}
+
+impl MustacheJson5Renderer {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            parser: template::new_parser()?,
+            partials: HashMap::new(),
+            partial_paths: HashMap::new(),
+            resolver: None,
+            escaper: Box::new(EscapeMode::default()),
+        })
+    }
+
+    /// Use a custom escaper for `{{name}}` interpolations
+    ///
+    /// [`EscapeMode`] implements [`Escaper`], so the built-in strategies can
+    /// be selected with e.g. `set_escaper(EscapeMode::Html)`.
+    pub fn set_escaper<E: Escaper + 'static>(&mut self, escaper: E) {
+        self.escaper = Box::new(escaper);
+    }
+
+    /// Look up partials that were not added with [`add_partial`] through
+    /// `resolver`
+    ///
+    /// [`add_partial`]: Self::add_partial
+    pub fn set_resolver<R: PartialResolver + 'static>(&mut self, resolver: R) {
+        self.resolver = Some(Box::new(resolver));
+    }
+
+    /// Register a partial available as `{{> name}}`
+    pub fn add_partial(&mut self, name: &str, source: &str) -> Result<()> {
+        let partial = Template::parse_with(&mut self.parser, source)
+            .with_context(|| format!("Failed to parse partial `{}`", name))?;
+        self.partials.insert(name.to_string(), partial);
+        Ok(())
+    }
+
+    /// Resolve every partial `template` includes, directly or through other
+    /// partials
+    ///
+    /// Fails if a partial includes itself without a section in between, since
+    /// rendering it could never finish. Unknown partials are left out and
+    /// render as nothing.
+    pub fn load_partials(&mut self, template: &Template) -> Result<()> {
+        let mut pending: Vec<String> = template
+            .partial_references()
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        let mut missing = HashSet::new();
+
+        while let Some(name) = pending.pop() {
+            if self.partials.contains_key(&name) || missing.contains(&name) {
+                continue;
+            }
+            let resolved = match &self.resolver {
+                Some(resolver) => resolver.resolve(&name)?,
+                None => None,
+            };
+            let Some(resolved) = resolved else {
+                missing.insert(name);
+                continue;
+            };
+
+            let partial = Template::parse_with(&mut self.parser, &resolved.source)
+                .with_context(|| format!("Failed to parse partial `{}`", name))?;
+            pending.extend(
+                partial
+                    .partial_references()
+                    .into_iter()
+                    .map(|(name, _)| name.to_string()),
+            );
+            if let Some(path) = resolved.path {
+                self.partial_paths.insert(name.clone(), path);
+            }
+            self.partials.insert(name, partial);
+        }
+
+        let edges = self
+            .partials
+            .iter()
+            .map(|(name, partial)| {
+                let references = partial
+                    .partial_references()
+                    .into_iter()
+                    .map(|(name, guarded)| (name.to_string(), guarded))
+                    .collect();
+                (name.clone(), references)
+            })
+            .collect();
+        let roots: Vec<String> = template
+            .partial_references()
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        partials::check_cycles(&edges, &roots)
+    }
+
+    /// Parse `source` and load the partials it needs
+    pub fn compile(&mut self, source: &str) -> Result<Template> {
+        let template = Template::parse_with(&mut self.parser, source)?;
+        self.load_partials(&template)?;
+        Ok(template)
+    }
+
+    /// Parse and render `source` with `data` as the root context
+    pub fn render(&mut self, source: &str, data: &Value) -> Result<String> {
+        let template = self.compile(source)?;
+        self.render_template(&template, data)
+    }
+
+    /// Render an already compiled template
+    ///
+    /// Only partials that are already loaded are available; see
+    /// [`load_partials`](Self::load_partials).
+    pub fn render_template(&self, template: &Template, data: &Value) -> Result<String> {
+        Ok(self.render_output(template, data, false)?.output)
+    }
+
+    /// Render a compiled template, recording the origin of the output
+    pub fn render_mapped(&self, template: &Template, data: &Value) -> Result<Rendered> {
+        self.render_output(template, data, true)
+    }
+
+    /// Look up a loaded partial
+    pub fn partial(&self, name: &str) -> Option<&Template> {
+        self.partials.get(name)
+    }
+
+    /// File a loaded partial was read from, if it came from one
+    pub fn partial_path(&self, name: &str) -> Option<&Path> {
+        self.partial_paths.get(name).map(PathBuf::as_path)
+    }
+
+    fn render_output(&self, template: &Template, data: &Value, track: bool) -> Result<Rendered> {
+        let mut output = Output::new(template.source().len(), track);
+        let mut context = Context::new(data);
+        self.render_nodes(template, template.nodes(), &mut context, &mut output, 0)?;
+        Ok(output.rendered)
+    }
+
+    fn render_nodes(
+        &self,
+        template: &Template,
+        nodes: &[Node],
+        context: &mut Context,
+        output: &mut Output,
+        depth: usize,
+    ) -> Result<()> {
+        for node in nodes {
+            let start = output.rendered.output.len();
+            match node {
+                Node::Text(range) => {
+                    output
+                        .rendered
+                        .output
+                        .push_str(&template.source()[range.clone()]);
+                    output.record(start, range.clone(), false);
+                }
+                Node::Comment(_) => {}
+                Node::Variable(variable) => {
+                    let value = context.lookup(&variable.path);
+                    let text = &mut output.rendered.output;
+                    if variable.escaped {
+                        self.escaper.escape(value, variable.placement, text);
+                    } else {
+                        text.push_str(&stringify(value));
+                    }
+                    output.record(start, variable.span.clone(), true);
+                }
+                Node::Section(section) => {
+                    self.render_section(template, section, context, output, depth)?
+                }
+                Node::Partial(partial) => {
+                    if depth >= MAX_PARTIAL_DEPTH {
+                        let (line, column) = template.line_col(partial.span.start);
+                        bail!(
+                            "Partial `{}` at line {}, column {} exceeds the maximum nesting depth of {}",
+                            partial.name,
+                            line,
+                            column,
+                            MAX_PARTIAL_DEPTH
+                        );
+                    }
+                    // Missing partials render as empty, as in the mustache spec
+                    if let Some(included) = self.partials.get(&partial.name) {
+                        let outer_partial = output.partial.replace(partial.name.clone());
+                        let outer_section = output.section.take();
+                        let result = self.render_nodes(
+                            included,
+                            included.nodes(),
+                            context,
+                            output,
+                            depth + 1,
+                        );
+                        output.partial = outer_partial;
+                        output.section = outer_section;
+                        result?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn render_section(
+        &self,
+        template: &Template,
+        section: &Section,
+        context: &mut Context,
+        output: &mut Output,
+        depth: usize,
+    ) -> Result<()> {
+        let outer = output.section.replace(section.open.clone());
+        let result = self.render_section_body(template, section, context, output, depth);
+        output.section = outer;
+        result
+    }
+
+    fn render_section_body(
+        &self,
+        template: &Template,
+        section: &Section,
+        context: &mut Context,
+        output: &mut Output,
+        depth: usize,
+    ) -> Result<()> {
+        let value = context.lookup(&section.path()).cloned();
+        let truthy = is_truthy(value.as_ref());
+
+        if section.inverted {
+            if !truthy {
+                self.render_nodes(template, &section.body, context, output, depth)?;
+            }
+            return Ok(());
+        }
+
+        let mut render_with =
+            |context: &mut Context, frame: Option<Value>, params: Vec<(String, Value)>| {
+                let pushed = frame.is_some();
+                if let Some(frame) = frame {
+                    context.push(Cow::Owned(frame));
+                }
+                let named = !params.is_empty();
+                if named {
+                    context.push_params(params);
+                }
+                let result = self.render_nodes(template, &section.body, context, output, depth);
+                if named {
+                    context.pop();
+                }
+                if pushed {
+                    context.pop();
+                }
+                result
+            };
+
+        match (section.helper(), section.expression.is_some()) {
+            (Some("if"), _) if truthy => render_with(context, None, Vec::new())?,
+            (Some("unless"), _) if !truthy => render_with(context, None, Vec::new())?,
+            (Some("if" | "unless"), _) => {}
+            (Some("with"), _) if truthy => {
+                let value = value.unwrap_or_default();
+                let params = block_params(section, vec![value.clone()]);
+                render_with(context, Some(value), params)?
+            }
+            (Some("with"), _) => {}
+            (Some("each"), _) => match value {
+                Some(Value::Array(items)) => {
+                    let count = items.len();
+                    for (index, item) in items.into_iter().enumerate() {
+                        let mut params =
+                            block_params(section, vec![item.clone(), Value::from(index)]);
+                        params.extend(loop_variables(index, count));
+                        render_with(context, Some(item), params)?;
+                    }
+                }
+                Some(Value::Object(map)) => {
+                    let count = map.len();
+                    for (index, (key, item)) in map.into_iter().enumerate() {
+                        let mut params =
+                            block_params(section, vec![item.clone(), Value::from(key.clone())]);
+                        params.extend(loop_variables(index, count));
+                        params.push(("@key".to_string(), Value::from(key)));
+                        render_with(context, Some(item), params)?;
+                    }
+                }
+                _ => {}
+            },
+            (_, true) => {
+                let (line, column) = template.line_col(section.open.start);
+                bail!(
+                    "Unknown block helper `{}` at line {}, column {}",
+                    section.name,
+                    line,
+                    column
+                );
+            }
+            (None, false) => match value {
+                Some(Value::Array(items)) => {
+                    let count = items.len();
+                    for (index, item) in items.into_iter().enumerate() {
+                        render_with(context, Some(item), loop_variables(index, count))?;
+                    }
+                }
+                Some(value) if truthy => render_with(context, Some(value), Vec::new())?,
+                _ => {}
+            },
+            (Some(_), false) => unreachable!("helpers always have an expression"),
+        }
+
+        Ok(())
+    }
+}
+
+/// Bind `as |a b|` parameters of `section` to `values`, in order
+fn block_params(section: &Section, values: Vec<Value>) -> Vec<(String, Value)> {
+    section.parameters.iter().cloned().zip(values).collect()
+}
+
+/// `@index`, `@first` and `@last` for one iteration of a loop
+pub fn loop_variables(index: usize, count: usize) -> Vec<(String, Value)> {
+    vec![
+        ("@index".to_string(), Value::from(index)),
+        ("@first".to_string(), Value::from(index == 0)),
+        ("@last".to_string(), Value::from(index + 1 == count)),
+    ]
+}
+
+/// Text substituted for a variable
+pub fn stringify(value: Option<&Value>) -> Cow<'_, str> {
+    match value {
+        None | Some(Value::Null) => Cow::Borrowed(""),
+        Some(Value::String(s)) => Cow::Borrowed(s),
+        Some(value) => Cow::Owned(value.to_string()),
+    }
+}
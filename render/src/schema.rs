@@ -0,0 +1,465 @@
+//! Check template variables against a JSON Schema for the render context
+//!
+//! Every variable and section name is resolved the way the renderer would:
+//! the first segment through the stack of section contexts and block
+//! parameters, the rest through object properties. Names the schema does not
+//! declare are reported, as are `each` sections over values that cannot be
+//! iterated.
+//!
+//! Objects that list `properties` are treated as closed unless
+//! `additionalProperties` is `true` or a schema, so typos are caught even when
+//! the schema leaves it out. Objects without `properties` accept any name
+//! unless `additionalProperties` is `false`. `$ref`
+//! pointers within the document, `anyOf`, `oneOf` and `allOf` are followed.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::fmt;
+use std::ops::Range;
+
+use crate::renderer::{MustacheJson5Renderer, MAX_PARTIAL_DEPTH};
+use crate::template::{Node, Path, Section, Template};
+
+/// A reference the schema does not allow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaIssue {
+    pub message: String,
+    /// Partial the reference is in, or `None` for the checked template
+    pub partial: Option<String>,
+    /// Byte range of the offending tag
+    pub span: Range<usize>,
+    /// One-based position of the tag
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for SchemaIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)?;
+        if let Some(partial) = &self.partial {
+            write!(f, " of partial `{}`", partial)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl MustacheJson5Renderer {
+    /// Check the names used in `source` and its partials against `schema`
+    pub fn check_schema(&mut self, source: &str, schema: &Value) -> Result<Vec<SchemaIssue>> {
+        let template = self.compile(source)?;
+        Ok(self.check_template_schema(&template, schema))
+    }
+
+    /// Check a compiled template; only loaded partials are followed
+    pub fn check_template_schema(&self, template: &Template, schema: &Value) -> Vec<SchemaIssue> {
+        let mut checker = Checker {
+            renderer: self,
+            root: schema,
+            scopes: vec![Scope::Value(schema.clone())],
+            partials: Vec::new(),
+            issues: Vec::new(),
+        };
+        checker.nodes(template, template.nodes());
+        checker.issues
+    }
+}
+
+/// One level of the static context stack
+#[derive(Debug, Clone)]
+enum Scope {
+    Value(Value),
+    Params(Vec<(String, Value)>),
+}
+
+/// Outcome of looking up a property in a schema
+enum Lookup {
+    Found(Value),
+    Missing,
+    /// The schema does not say, so nothing can be checked
+    Unknown,
+}
+
+struct Checker<'a> {
+    renderer: &'a MustacheJson5Renderer,
+    root: &'a Value,
+    scopes: Vec<Scope>,
+    /// Partials being checked, innermost last
+    partials: Vec<String>,
+    issues: Vec<SchemaIssue>,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, template: &Template, span: Range<usize>, message: String) {
+        let (line, column) = template.line_col(span.start);
+        self.issues.push(SchemaIssue {
+            message,
+            partial: self.partials.last().cloned(),
+            span,
+            line,
+            column,
+        });
+    }
+
+    fn nodes(&mut self, template: &Template, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Variable(variable) => {
+                    if let Err(message) = self.resolve(&variable.path) {
+                        self.report(template, variable.span.clone(), message);
+                    }
+                }
+                Node::Section(section) => self.section(template, section),
+                Node::Partial(partial) => {
+                    // Recursive partials are checked once per chain
+                    if self.partials.len() >= MAX_PARTIAL_DEPTH
+                        || self.partials.contains(&partial.name)
+                    {
+                        continue;
+                    }
+                    if let Some(included) = self.renderer.partial(&partial.name) {
+                        self.partials.push(partial.name.clone());
+                        self.nodes(included, included.nodes());
+                        self.partials.pop();
+                    }
+                }
+                Node::Text(_) | Node::Comment(_) => {}
+            }
+        }
+    }
+
+    fn section(&mut self, template: &Template, section: &Section) {
+        let path = section.path();
+        let schema = match self.resolve(&path) {
+            Ok(schema) => schema,
+            Err(message) => {
+                self.report(template, section.open.clone(), message);
+                None
+            }
+        };
+        let schema = schema.unwrap_or(Value::Bool(true));
+
+        let mut pushed = 0;
+        match (section.inverted, section.helper()) {
+            (true, _) | (false, Some("if" | "unless")) => {}
+            (false, Some("with")) => {
+                self.scopes.push(Scope::Value(schema.clone()));
+                self.scopes
+                    .push(Scope::Params(params(section, vec![schema])));
+                pushed = 2;
+            }
+            (false, Some("each")) => {
+                let (item, key) = match iteration(self.root, &schema) {
+                    Some(iteration) => iteration,
+                    None => {
+                        let message = format!(
+                            "`{{{{#each {}}}}}` iterates over `{}`, which is {}",
+                            path,
+                            path,
+                            describe_type(self.root, &schema)
+                        );
+                        self.report(template, section.open.clone(), message);
+                        (Value::Bool(true), Value::Bool(true))
+                    }
+                };
+                self.scopes.push(Scope::Value(item.clone()));
+                self.scopes
+                    .push(Scope::Params(params(section, vec![item, key])));
+                pushed = 2;
+            }
+            (false, _) => {
+                let item = array_items(self.root, &schema).unwrap_or(schema);
+                self.scopes.push(Scope::Value(item));
+                pushed = 1;
+            }
+        }
+
+        self.nodes(template, &section.body);
+        self.scopes.truncate(self.scopes.len() - pushed);
+    }
+
+    /// Resolve `path` to its schema; `Ok(None)` means it cannot be checked
+    fn resolve(&self, path: &Path) -> Result<Option<Value>, String> {
+        let Some((first, rest)) = path.0.split_first() else {
+            return Ok(None);
+        };
+        // Loop variables like `@index` are provided by the renderer
+        if first.starts_with('@') {
+            return Ok(None);
+        }
+
+        let mut found = None;
+        for scope in self.scopes.iter().rev() {
+            match scope {
+                Scope::Params(params) => {
+                    if let Some((_, schema)) = params.iter().find(|(name, _)| name == first) {
+                        found = Some(schema.clone());
+                        break;
+                    }
+                }
+                Scope::Value(schema) => match property(self.root, schema, first) {
+                    Lookup::Found(schema) => {
+                        found = Some(schema);
+                        break;
+                    }
+                    Lookup::Unknown => return Ok(None),
+                    Lookup::Missing => {}
+                },
+            }
+        }
+        let Some(mut schema) = found else {
+            return Err(format!("`{}` is not defined in the schema", first));
+        };
+
+        for (i, segment) in rest.iter().enumerate() {
+            let parent = path.0[..=i].join(".");
+            let lookup = match segment.parse::<usize>() {
+                Ok(_) if array_items(self.root, &schema).is_some() => {
+                    Lookup::Found(array_items(self.root, &schema).unwrap_or_default())
+                }
+                _ => property(self.root, &schema, segment),
+            };
+            schema = match lookup {
+                Lookup::Found(schema) => schema,
+                Lookup::Unknown => return Ok(None),
+                Lookup::Missing => {
+                    return Err(format!(
+                        "`{}` has no property `{}` (in `{}`)",
+                        parent, segment, path
+                    ))
+                }
+            };
+        }
+        Ok(Some(schema))
+    }
+}
+
+/// Bind `as |a b|` parameters to the schemas of their values
+fn params(section: &Section, schemas: Vec<Value>) -> Vec<(String, Value)> {
+    section.parameters.iter().cloned().zip(schemas).collect()
+}
+
+/// Follow `$ref` pointers within the schema document
+fn deref<'a>(root: &'a Value, mut schema: &'a Value) -> &'a Value {
+    for _ in 0..32 {
+        let Some(pointer) = schema.get("$ref").and_then(Value::as_str) else {
+            break;
+        };
+        match pointer
+            .strip_prefix('#')
+            .and_then(|pointer| root.pointer(pointer))
+        {
+            Some(target) => schema = target,
+            None => break,
+        }
+    }
+    schema
+}
+
+/// The subschemas of `anyOf`, `oneOf` and `allOf`
+fn branches<'a>(root: &'a Value, schema: &'a Value) -> Vec<&'a Value> {
+    ["anyOf", "oneOf", "allOf"]
+        .iter()
+        .filter_map(|key| schema.get(key).and_then(Value::as_array))
+        .flatten()
+        .map(|branch| deref(root, branch))
+        .collect()
+}
+
+fn types(schema: &Value) -> Option<Vec<&str>> {
+    match schema.get("type")? {
+        Value::String(name) => Some(vec![name.as_str()]),
+        Value::Array(names) => Some(names.iter().filter_map(Value::as_str).collect()),
+        _ => None,
+    }
+}
+
+fn property(root: &Value, schema: &Value, name: &str) -> Lookup {
+    let schema = deref(root, schema);
+    let branches = branches(root, schema);
+    if !branches.is_empty() {
+        let mut unknown = false;
+        for branch in branches {
+            match property(root, branch, name) {
+                Lookup::Found(found) => return Lookup::Found(found),
+                Lookup::Unknown => unknown = true,
+                Lookup::Missing => {}
+            }
+        }
+        if unknown && schema.get("properties").is_none() {
+            return Lookup::Unknown;
+        }
+    }
+
+    let Some(object) = schema.as_object() else {
+        // `true` allows anything; `false` allows nothing
+        return if schema == &Value::Bool(false) {
+            Lookup::Missing
+        } else {
+            Lookup::Unknown
+        };
+    };
+    if let Some(types) = types(schema) {
+        if !types.contains(&"object") {
+            return Lookup::Missing;
+        }
+    }
+    if let Some(found) = object
+        .get("properties")
+        .and_then(|properties| properties.get(name))
+    {
+        return Lookup::Found(found.clone());
+    }
+    if object.contains_key("patternProperties") {
+        return Lookup::Unknown;
+    }
+
+    match object.get("additionalProperties") {
+        Some(Value::Bool(false)) => Lookup::Missing,
+        Some(Value::Bool(true)) => Lookup::Unknown,
+        Some(additional) => Lookup::Found(additional.clone()),
+        None if object.contains_key("properties") => Lookup::Missing,
+        None => Lookup::Unknown,
+    }
+}
+
+/// Schema of the items of an array schema
+fn array_items(root: &Value, schema: &Value) -> Option<Value> {
+    let schema = deref(root, schema);
+    if let Some(items) = schema.get("items") {
+        return Some(items.clone());
+    }
+    if let Some(types) = types(schema) {
+        return types.contains(&"array").then_some(Value::Bool(true));
+    }
+    branches(root, schema)
+        .into_iter()
+        .find_map(|branch| array_items(root, branch))
+}
+
+/// Item and key schemas for `each`, or `None` if the value cannot be iterated
+fn iteration(root: &Value, schema: &Value) -> Option<(Value, Value)> {
+    if let Some(items) = array_items(root, schema) {
+        return Some((items, serde_json::json!({"type": "integer"})));
+    }
+
+    let resolved = deref(root, schema);
+    let is_object = match types(resolved) {
+        Some(types) => types.contains(&"object"),
+        None => {
+            resolved.get("properties").is_some() || !resolved.is_object() || {
+                let branches = branches(root, resolved);
+                branches.is_empty()
+                    || branches
+                        .iter()
+                        .any(|branch| iteration(root, branch).is_some())
+            }
+        }
+    };
+    if !is_object || resolved == &Value::Bool(false) {
+        return None;
+    }
+
+    let item = resolved
+        .get("additionalProperties")
+        .filter(|additional| additional.is_object())
+        .cloned()
+        .unwrap_or(Value::Bool(true));
+    Some((item, serde_json::json!({"type": "string"})))
+}
+
+fn describe_type(root: &Value, schema: &Value) -> String {
+    match types(deref(root, schema)) {
+        Some(types) => format!("of type {}", types.join(" or ")),
+        None => "not an array or object".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn check(source: &str, schema: Value) -> Vec<String> {
+        MustacheJson5Renderer::new()
+            .unwrap()
+            .check_schema(source, &schema)
+            .unwrap()
+            .into_iter()
+            .map(|issue| issue.to_string())
+            .collect()
+    }
+
+    fn user_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "user": {
+                    "type": "object",
+                    "properties": {"name": {"type": "string"}, "tags": {"type": "array", "items": {"type": "string"}}}
+                },
+                "items": {"type": "array", "items": {"$ref": "#/$defs/item"}},
+                "title": {"type": "string"}
+            },
+            "$defs": {
+                "item": {"type": "object", "properties": {"id": {"type": "integer"}}}
+            }
+        })
+    }
+
+    #[test]
+    fn test_reports_unknown_properties() {
+        let issues = check(r#"{"a": "{{user.nmae}}", "b": {{titel}}}"#, user_schema());
+        assert_eq!(
+            issues,
+            vec![
+                "line 1, column 8: `user` has no property `nmae` (in `user.nmae`)",
+                "line 1, column 29: `titel` is not defined in the schema",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sections_push_item_schemas() {
+        let source = "[{{#items}}{{id}}{{title}}{{/items}}{{#each items as |item i|}}{{item.id}}{{i}}{{item.nope}}{{/each}}]";
+        assert_eq!(
+            check(source, user_schema()),
+            vec!["line 1, column 80: `item` has no property `nope` (in `item.nope`)"]
+        );
+    }
+
+    #[test]
+    fn test_each_over_non_iterable() {
+        let issues = check(
+            "{{#each title}}{{.}}{{/each}}{{#each user.tags}}{{.}}{{/each}}",
+            user_schema(),
+        );
+        assert_eq!(
+            issues,
+            vec!["line 1, column 1: `{{#each title}}` iterates over `title`, which is of type string"]
+        );
+    }
+
+    #[test]
+    fn test_open_schemas_are_not_checked() {
+        let schema = json!({
+            "type": "object",
+            "properties": {"meta": {"type": "object", "additionalProperties": true}},
+            "additionalProperties": {"type": "string"}
+        });
+        assert!(check("{{meta.anything}}{{other}}{{@index}}", schema).is_empty());
+        assert!(check("{{anything.at.all}}", json!({})).is_empty());
+
+        // An object without `properties` is open unless it says otherwise
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "meta": {"type": "object"},
+                "empty": {"type": "object", "additionalProperties": false}
+            }
+        });
+        assert_eq!(
+            check("{{meta.anything}}{{empty.nope}}", schema),
+            vec!["line 1, column 18: `empty` has no property `nope` (in `empty.nope`)"]
+        );
+    }
+}
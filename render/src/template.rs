@@ -0,0 +1,690 @@
+//! Compiled template model built from the tree-sitter syntax tree
+//!
+//! The grammar recognises mustache tags in JSON5 value positions, but tags
+//! inside a JSON5 string are part of the `string` token. Those are scanned
+//! separately here, so a compiled [`Template`] describes every tag regardless
+//! of where it appears.
+//!
+//! Some templates only become valid JSON5 once rendered, such as a partial in
+//! member position. The grammar cannot describe those, so when the tree has
+//! errors the whole source is scanned as text instead.
+
+use anyhow::{bail, Context, Result};
+use std::ops::Range;
+use tree_sitter::{Node as SyntaxNode, Parser, Tree};
+use tree_sitter_mustache_json5::LANGUAGE;
+
+/// Section tags handled as block helpers when followed by an expression,
+/// e.g. `{{#each items}}` or `{{#if enabled}}`
+pub const HELPERS: &[&str] = &["each", "if", "unless", "with"];
+
+/// A parsed template ready for rendering
+#[derive(Debug, Clone)]
+pub struct Template {
+    source: String,
+    nodes: Vec<Node>,
+}
+
+/// A piece of a compiled template
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    /// Template text copied to the output unchanged
+    Text(Range<usize>),
+    /// `{{name}}`, `{{{name}}}` or `{{&name}}`
+    Variable(Variable),
+    /// `{{#name}}...{{/name}}` or `{{^name}}...{{/name}}`
+    Section(Section),
+    /// `{{> name}}`
+    Partial(Partial),
+    /// `{{! comment }}`
+    Comment(Range<usize>),
+}
+
+/// A reference into the context, like `user.name` or `.`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Path(pub Vec<String>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    pub path: Path,
+    /// `false` for `{{{name}}}` and `{{&name}}`
+    pub escaped: bool,
+    pub placement: Placement,
+    /// Byte range of the whole tag
+    pub span: Range<usize>,
+}
+
+/// Where a variable appears in the JSON5 document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    /// In place of a value or other syntax
+    Value,
+    /// In place of an object member name
+    Key,
+    /// Inside a string delimited by `quote`
+    String { quote: char },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    /// The tag name, repeated in the closing tag
+    pub name: String,
+    /// Argument of a block helper, e.g. `items` in `{{#each items}}`
+    pub expression: Option<Path>,
+    /// Block parameters from `as |item index|`
+    pub parameters: Vec<String>,
+    pub inverted: bool,
+    pub body: Vec<Node>,
+    /// Byte range from the opening tag through the closing tag
+    pub span: Range<usize>,
+    /// Byte range of the opening tag
+    pub open: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Partial {
+    pub name: String,
+    pub span: Range<usize>,
+}
+
+impl Path {
+    /// Parse a dotted name; `.` is the current context
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        if text == "." {
+            return Path(Vec::new());
+        }
+        Path(text.split('.').map(str::to_string).collect())
+    }
+
+    /// Whether this is the implicit iterator `.`
+    pub fn is_current(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::fmt::Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_current() {
+            f.write_str(".")
+        } else {
+            f.write_str(&self.0.join("."))
+        }
+    }
+}
+
+impl Section {
+    /// Whether this section is a block helper such as `{{#each items}}`
+    pub fn helper(&self) -> Option<&str> {
+        self.expression
+            .as_ref()
+            .map(|_| self.name.as_str())
+            .filter(|name| HELPERS.contains(name))
+    }
+
+    /// The context path this section tests or iterates
+    pub fn path(&self) -> Path {
+        self.expression
+            .clone()
+            .unwrap_or_else(|| Path::parse(&self.name))
+    }
+}
+
+impl Template {
+    /// Parse a template with a new parser
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parser = new_parser()?;
+        Self::parse_with(&mut parser, source)
+    }
+
+    /// Parse a template, reusing `parser`
+    pub fn parse_with(parser: &mut Parser, source: &str) -> Result<Self> {
+        let tree = parser
+            .parse(source, None)
+            .context("Failed to parse template")?;
+        Self::from_tree(&tree, source)
+    }
+
+    /// Compile an existing syntax tree of `source`
+    pub fn from_tree(tree: &Tree, source: &str) -> Result<Self> {
+        let nodes = if tree.root_node().has_error() {
+            scan_inline(source, 0..source.len())?
+        } else {
+            let mut nodes = Vec::new();
+            Builder { source }.convert_children(tree.root_node(), 0..source.len(), &mut nodes)?;
+            nodes
+        };
+
+        Ok(Self {
+            source: source.to_string(),
+            nodes: merge_text(nodes),
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Names of the partials this template includes, each flagged with
+    /// whether the reference is inside a section
+    pub fn partial_references(&self) -> Vec<(&str, bool)> {
+        fn collect<'a>(nodes: &'a [Node], guarded: bool, out: &mut Vec<(&'a str, bool)>) {
+            for node in nodes {
+                match node {
+                    Node::Partial(partial) => out.push((&partial.name, guarded)),
+                    Node::Section(section) => collect(&section.body, true, out),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut references = Vec::new();
+        collect(&self.nodes, false, &mut references);
+        references
+    }
+
+    /// One-based line and column of a byte offset, for diagnostics
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        line_col(&self.source, offset.min(self.source.len()))
+    }
+}
+
+pub(crate) fn new_parser() -> Result<Parser> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&LANGUAGE.into())
+        .context("Failed to set language for parser")?;
+    Ok(parser)
+}
+
+struct Builder<'a> {
+    source: &'a str,
+}
+
+impl Builder<'_> {
+    fn convert(&self, node: SyntaxNode, out: &mut Vec<Node>) -> Result<()> {
+        let span = node.byte_range();
+
+        match node.kind() {
+            "mustache_interpolation" | "mustache_unescaped" => {
+                out.push(Node::Variable(Variable {
+                    path: self.expression(node).unwrap_or(Path(Vec::new())),
+                    escaped: node.kind() == "mustache_interpolation",
+                    placement: match node.parent() {
+                        Some(parent) if parent.kind() == "name" => Placement::Key,
+                        _ => Placement::Value,
+                    },
+                    span,
+                }));
+            }
+            "mustache_partial" => {
+                let name = self.child_text(node, "partial_name").unwrap_or_default();
+                out.push(Node::Partial(Partial {
+                    name: name.to_string(),
+                    span,
+                }));
+            }
+            "mustache_comment" => out.push(Node::Comment(span)),
+            "mustache_section" | "mustache_inverted_section" => {
+                out.push(Node::Section(self.section(node)?));
+            }
+            "string" => {
+                let text = &self.source[span.clone()];
+                if text.contains("{{") {
+                    out.extend(scan_inline(self.source, span)?);
+                } else {
+                    out.push(Node::Text(span));
+                }
+            }
+            _ if node.child_count() == 0 => out.push(Node::Text(span)),
+            _ => self.convert_children(node, span, out)?,
+        }
+
+        Ok(())
+    }
+
+    /// Convert the children of `node` within `range`, keeping the text between them
+    fn convert_children(
+        &self,
+        node: SyntaxNode,
+        range: Range<usize>,
+        out: &mut Vec<Node>,
+    ) -> Result<()> {
+        let mut position = range.start;
+        for child in node.children(&mut node.walk()) {
+            if child.end_byte() <= range.start || child.start_byte() >= range.end {
+                continue;
+            }
+            if child.start_byte() > position {
+                out.push(Node::Text(position..child.start_byte()));
+            }
+            self.convert(child, out)?;
+            position = child.end_byte();
+        }
+        if range.end > position {
+            out.push(Node::Text(position..range.end));
+        }
+        Ok(())
+    }
+
+    fn section(&self, node: SyntaxNode) -> Result<Section> {
+        let inverted = node.kind() == "mustache_inverted_section";
+        let mut cursor = node.walk();
+        let children: Vec<SyntaxNode> = node.named_children(&mut cursor).collect();
+
+        let (Some(begin), Some(end)) = (children.first(), children.last()) else {
+            bail!("Malformed section at byte {}", node.start_byte());
+        };
+
+        let name = self.child_text(*begin, "tag_name").unwrap_or_default();
+        let end_name = self.child_text(*end, "tag_name").unwrap_or_default();
+        if name != end_name {
+            let (line, column) = line_col(self.source, end.start_byte());
+            bail!(
+                "Section `{}` closed by `{{{{/{}}}}}` at line {}, column {}",
+                name,
+                end_name,
+                line,
+                column
+            );
+        }
+
+        let parameters = begin
+            .named_children(&mut begin.walk())
+            .filter(|child| child.kind() == "section_parameters")
+            .flat_map(|params| {
+                params
+                    .named_children(&mut params.walk())
+                    .map(|param| self.source[param.byte_range()].to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        let mut body = Vec::new();
+        let body_range = begin.end_byte()..end.start_byte();
+        self.convert_children(node, body_range.clone(), &mut body)?;
+        // The begin and end tags themselves fall outside the body range
+        body.retain(|child| !matches!(child, Node::Text(range) if range.is_empty()));
+
+        Ok(Section {
+            name: name.to_string(),
+            expression: self.expression(*begin),
+            parameters,
+            inverted,
+            body: merge_text(body),
+            span: node.byte_range(),
+            open: begin.byte_range(),
+        })
+    }
+
+    fn expression(&self, node: SyntaxNode) -> Option<Path> {
+        node.named_children(&mut node.walk())
+            .find(|child| {
+                matches!(
+                    child.kind(),
+                    "path_expression" | "identifier_expression" | "dot_expression"
+                )
+            })
+            .map(|child| Path::parse(&self.source[child.byte_range()]))
+    }
+
+    fn child_text(&self, node: SyntaxNode, kind: &str) -> Option<&str> {
+        node.named_children(&mut node.walk())
+            .find(|child| child.kind() == kind)
+            .map(|child| &self.source[child.byte_range()])
+    }
+}
+
+fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+    (line, column)
+}
+
+/// Scan mustache tags in text the grammar treats as opaque
+fn scan_inline(source: &str, span: Range<usize>) -> Result<Vec<Node>> {
+    // Open sections, innermost last, with the nodes collected so far
+    let mut stack: Vec<(Section, Vec<Node>)> = Vec::new();
+    let mut out = Vec::new();
+    let mut position = span.start;
+    let mut lexer = Json5Lexer::default();
+
+    while let Some(offset) = source[position..span.end].find("{{") {
+        let tag_start = position + offset;
+        let current = stack.last_mut().map(|(_, nodes)| nodes).unwrap_or(&mut out);
+        if tag_start > position {
+            current.push(Node::Text(position..tag_start));
+            lexer.feed(&source[position..tag_start]);
+        }
+        let placement = lexer.placement();
+
+        let triple = source[tag_start..].starts_with("{{{");
+        let (content_start, close) = if triple {
+            (tag_start + 3, "}}}")
+        } else {
+            (tag_start + 2, "}}")
+        };
+        let Some(close_offset) = source[content_start..span.end].find(close) else {
+            let (line, column) = line_col(source, tag_start);
+            bail!("Unclosed mustache tag at line {}, column {}", line, column);
+        };
+        let tag_end = content_start + close_offset + close.len();
+        let content = source[content_start..content_start + close_offset].trim();
+        let tag_span = tag_start..tag_end;
+        position = tag_end;
+
+        if triple {
+            current.push(Node::Variable(Variable {
+                path: Path::parse(content),
+                escaped: false,
+                placement,
+                span: tag_span,
+            }));
+            continue;
+        }
+
+        let mut chars = content.chars();
+        let sigil = chars.next();
+        let rest = chars.as_str().trim();
+
+        match sigil {
+            Some('!') => current.push(Node::Comment(tag_span)),
+            Some('>') => current.push(Node::Partial(Partial {
+                name: rest.to_string(),
+                span: tag_span,
+            })),
+            Some('&') => current.push(Node::Variable(Variable {
+                path: Path::parse(rest),
+                escaped: false,
+                placement,
+                span: tag_span,
+            })),
+            Some(sigil @ ('#' | '^')) => {
+                let (name, expression, parameters) = parse_section_tag(rest);
+                stack.push((
+                    Section {
+                        name,
+                        expression,
+                        parameters,
+                        inverted: sigil == '^',
+                        body: Vec::new(),
+                        span: tag_span.clone(),
+                        open: tag_span,
+                    },
+                    Vec::new(),
+                ));
+            }
+            Some('/') => {
+                let Some((mut section, body)) = stack.pop() else {
+                    let (line, column) = line_col(source, tag_start);
+                    bail!(
+                        "Closing tag `{{{{/{}}}}}` without an open section at line {}, column {}",
+                        rest,
+                        line,
+                        column
+                    );
+                };
+                if section.name != rest {
+                    let (line, column) = line_col(source, tag_start);
+                    bail!(
+                        "Section `{}` closed by `{{{{/{}}}}}` at line {}, column {}",
+                        section.name,
+                        rest,
+                        line,
+                        column
+                    );
+                }
+                section.body = merge_text(body);
+                section.span = section.open.start..tag_end;
+                stack
+                    .last_mut()
+                    .map(|(_, nodes)| nodes)
+                    .unwrap_or(&mut out)
+                    .push(Node::Section(section));
+            }
+            _ => current.push(Node::Variable(Variable {
+                path: Path::parse(content),
+                escaped: true,
+                placement,
+                span: tag_span,
+            })),
+        }
+    }
+
+    if let Some((section, _)) = stack.last() {
+        let (line, column) = line_col(source, section.open.start);
+        bail!(
+            "Unclosed section `{}` at line {}, column {}",
+            section.name,
+            line,
+            column
+        );
+    }
+
+    if span.end > position {
+        out.push(Node::Text(position..span.end));
+    }
+    Ok(out)
+}
+
+/// Tracks whether scanned text is inside a JSON5 string or comment
+#[derive(Debug, Default, Clone, Copy)]
+enum Json5Lexer {
+    #[default]
+    Code,
+    String {
+        quote: char,
+        escaped: bool,
+    },
+    LineComment,
+    BlockComment,
+    /// Saw `/` in code, which may start a comment
+    Slash,
+    /// Saw `*` in a block comment, which may end it
+    Star,
+}
+
+impl Json5Lexer {
+    fn feed(&mut self, text: &str) {
+        for c in text.chars() {
+            *self = match (*self, c) {
+                (Json5Lexer::Code | Json5Lexer::Slash, '"' | '\'') => Json5Lexer::String {
+                    quote: c,
+                    escaped: false,
+                },
+                (Json5Lexer::Code, '/') => Json5Lexer::Slash,
+                (Json5Lexer::Slash, '/') => Json5Lexer::LineComment,
+                (Json5Lexer::Slash, '*') => Json5Lexer::BlockComment,
+                (Json5Lexer::Code | Json5Lexer::Slash, _) => Json5Lexer::Code,
+                (
+                    Json5Lexer::String {
+                        quote,
+                        escaped: false,
+                    },
+                    '\\',
+                ) => Json5Lexer::String {
+                    quote,
+                    escaped: true,
+                },
+                (
+                    Json5Lexer::String {
+                        quote,
+                        escaped: false,
+                    },
+                    c,
+                ) if c == quote => Json5Lexer::Code,
+                (Json5Lexer::String { quote, .. }, _) => Json5Lexer::String {
+                    quote,
+                    escaped: false,
+                },
+                (Json5Lexer::LineComment, '\n') => Json5Lexer::Code,
+                (Json5Lexer::LineComment, _) => Json5Lexer::LineComment,
+                (Json5Lexer::BlockComment | Json5Lexer::Star, '*') => Json5Lexer::Star,
+                (Json5Lexer::Star, '/') => Json5Lexer::Code,
+                (Json5Lexer::BlockComment | Json5Lexer::Star, _) => Json5Lexer::BlockComment,
+            };
+        }
+    }
+
+    fn placement(&self) -> Placement {
+        match self {
+            Json5Lexer::String { quote, .. } => Placement::String { quote: *quote },
+            _ => Placement::Value,
+        }
+    }
+}
+
+/// Split the content of `{{#each items as |item index|}}` after the sigil
+fn parse_section_tag(content: &str) -> (String, Option<Path>, Vec<String>) {
+    let (head, parameters) = match content.split_once(" as ") {
+        Some((head, params)) => {
+            let params = params.trim().trim_matches('|');
+            (
+                head,
+                params.split_whitespace().map(str::to_string).collect(),
+            )
+        }
+        None => (content, Vec::new()),
+    };
+
+    let mut words = head.split_whitespace();
+    let name = words.next().unwrap_or_default().to_string();
+    let expression = words.next().map(Path::parse);
+    (name, expression, parameters)
+}
+
+/// Join adjacent text ranges so rendering copies larger slices
+fn merge_text(nodes: Vec<Node>) -> Vec<Node> {
+    let mut merged: Vec<Node> = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        if let (Some(Node::Text(previous)), Node::Text(range)) = (merged.last_mut(), &node) {
+            if previous.end == range.start {
+                previous.end = range.end;
+                continue;
+            }
+        }
+        merged.push(node);
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable_paths(nodes: &[Node]) -> Vec<String> {
+        let mut paths = Vec::new();
+        for node in nodes {
+            match node {
+                Node::Variable(variable) => paths.push(variable.path.to_string()),
+                Node::Section(section) => paths.extend(variable_paths(&section.body)),
+                _ => {}
+            }
+        }
+        paths
+    }
+
+    #[test]
+    fn test_parse_value_and_string_tags() {
+        let template = Template::parse(r#"{"a": {{count}}, "b": "Hi {{user.name}}!"}"#).unwrap();
+        assert_eq!(variable_paths(template.nodes()), vec!["count", "user.name"]);
+    }
+
+    #[test]
+    fn test_parse_section_with_parameters() {
+        let template =
+            Template::parse("{{#each users as |user i|}}{{user.name}}{{/each}}").unwrap();
+        let Node::Section(section) = &template.nodes()[0] else {
+            panic!("expected a section");
+        };
+
+        assert_eq!(section.name, "each");
+        assert_eq!(section.helper(), Some("each"));
+        assert_eq!(section.expression, Some(Path::parse("users")));
+        assert_eq!(section.parameters, vec!["user", "i"]);
+        assert_eq!(variable_paths(&section.body), vec!["user.name"]);
+    }
+
+    #[test]
+    fn test_parse_inline_section() {
+        let template = Template::parse(r#"{"a": "{{#x}}{{{y}}}{{/x}}"}"#).unwrap();
+        let section = template
+            .nodes()
+            .iter()
+            .find_map(|node| match node {
+                Node::Section(section) => Some(section),
+                _ => None,
+            })
+            .expect("expected a section");
+
+        assert_eq!(section.name, "x");
+        assert!(matches!(
+            &section.body[0],
+            Node::Variable(Variable { escaped: false, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Template::parse(r#"{"a": "{{#x}}y{{/z}}"}"#).is_err());
+        assert!(Template::parse(r#"{"a": "{{x"}"#).is_err());
+        assert!(Template::parse("[{{#items}}1]").is_err());
+        assert!(Template::parse("[1{{/items}}]").is_err());
+    }
+
+    fn placements(nodes: &[Node]) -> Vec<Placement> {
+        let mut found = Vec::new();
+        for node in nodes {
+            match node {
+                Node::Variable(variable) => found.push(variable.placement),
+                Node::Section(section) => found.extend(placements(&section.body)),
+                _ => {}
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn test_variable_placement() {
+        let double = Placement::String { quote: '"' };
+        let single = Placement::String { quote: '\'' };
+
+        let template =
+            Template::parse(r#"{"a": {{x}}, "b": "{{y}}", c: 'it\'s {{z}}', {{k}}: 1}"#).unwrap();
+        assert_eq!(
+            placements(template.nodes()),
+            vec![Placement::Value, double, single, Placement::Key]
+        );
+
+        // Falls back to scanning, which tracks strings and comments itself
+        let template =
+            Template::parse("{ {{> p}}, // \"{{a}}\n \"k\": \"{{#s}}{{b}}{{/s}}\", v: {{c}} }")
+                .unwrap();
+        assert_eq!(
+            placements(template.nodes()),
+            vec![Placement::Value, double, Placement::Value]
+        );
+    }
+
+    #[test]
+    fn test_parse_falls_back_to_scanning() {
+        let template = Template::parse(r#"{ {{> header}}, "a": {{#x}}1{{/x}} }"#).unwrap();
+        assert!(matches!(&template.nodes()[1], Node::Partial(p) if p.name == "header"));
+        assert!(template
+            .nodes()
+            .iter()
+            .any(|node| matches!(node, Node::Section(s) if s.name == "x")));
+    }
+
+    #[test]
+    fn test_text_round_trips_without_tags() {
+        let source = "{\n  \"a\": [1, 2],\n  // comment\n  b: 'x'\n}\n";
+        let template = Template::parse(source).unwrap();
+        assert_eq!(template.nodes(), &[Node::Text(0..source.len())]);
+    }
+}
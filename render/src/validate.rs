@@ -0,0 +1,509 @@
+//! Check that rendered output is valid JSON5
+//!
+//! Syntax errors in the output are mapped back to the template text or tag
+//! that produced them, e.g. "trailing comma produced by `{{#items}}` at
+//! line 12", so broken generated documents are caught before they ship.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::fmt;
+
+use crate::renderer::{MustacheJson5Renderer, Rendered, Segment};
+use crate::template::Template;
+
+/// Options for [`MustacheJson5Renderer::render_validated`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidateOptions {
+    /// Require plain JSON: no comments, single quotes, unquoted keys,
+    /// trailing commas or JSON5-only numbers
+    pub strict_json: bool,
+}
+
+/// A syntax error in rendered output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// Byte offset in the checked text
+    pub offset: usize,
+    pub message: String,
+}
+
+/// A syntax error in rendered output, located in the template
+#[derive(Debug, Clone)]
+pub struct ValidationError {
+    pub message: String,
+    /// One-based position in the rendered output
+    pub output_line: usize,
+    pub output_column: usize,
+    /// Description of the template node that produced the error, like
+    /// "produced by `{{#items}}` at line 12"
+    pub origin: Option<String>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(origin) = &self.origin {
+            write!(f, " {}", origin)?;
+        }
+        write!(
+            f,
+            " (rendered output line {}, column {})",
+            self.output_line, self.output_column
+        )
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl MustacheJson5Renderer {
+    /// Render `source` and check that the result is valid JSON5
+    ///
+    /// Syntax errors are returned as a [`ValidationError`] describing the
+    /// template node that produced the offending output.
+    pub fn render_validated(
+        &mut self,
+        source: &str,
+        data: &Value,
+        options: &ValidateOptions,
+    ) -> Result<String> {
+        let template = self.compile(source)?;
+        let rendered = self.render_mapped(&template, data)?;
+
+        if let Err(error) = check(&rendered.output, options) {
+            return Err(self.locate(&template, &rendered, error).into());
+        }
+        Ok(rendered.output)
+    }
+
+    pub(crate) fn locate(
+        &self,
+        template: &Template,
+        rendered: &Rendered,
+        error: SyntaxError,
+    ) -> ValidationError {
+        let (output_line, output_column) = line_col(&rendered.output, error.offset);
+        let origin = rendered
+            .segment_at(error.offset)
+            .and_then(|segment| self.describe(template, segment, error.offset));
+
+        ValidationError {
+            message: error.message,
+            output_line,
+            output_column,
+            origin,
+        }
+    }
+
+    fn describe(&self, template: &Template, segment: &Segment, offset: usize) -> Option<String> {
+        let template = match &segment.partial {
+            Some(name) => self.partial(name)?,
+            None => template,
+        };
+        let source = template.source();
+        let suffix = match &segment.partial {
+            Some(name) => format!(" of partial `{}`", name),
+            None => String::new(),
+        };
+
+        // Variables and repeated section bodies are blamed on their tag;
+        // plain text is located directly
+        let tag = if segment.variable {
+            Some(segment.source.clone())
+        } else {
+            segment.section.clone()
+        };
+
+        Some(match tag {
+            Some(tag) => {
+                let (line, _) = template.line_col(tag.start);
+                format!("produced by `{}` at line {}{}", &source[tag], line, suffix)
+            }
+            None => {
+                let position = segment.source.start + (offset - segment.output.start);
+                let (line, column) = template.line_col(position.min(segment.source.end));
+                format!("at template line {}, column {}{}", line, column, suffix)
+            }
+        })
+    }
+}
+
+fn line_col(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+    (line, column)
+}
+
+/// Check that `text` is a single valid JSON5 (or JSON) document
+pub fn check(text: &str, options: &ValidateOptions) -> Result<(), SyntaxError> {
+    let mut checker = Checker {
+        text,
+        bytes: text.as_bytes(),
+        position: 0,
+        strict: options.strict_json,
+    };
+
+    checker.skip_trivia()?;
+    if checker.position == text.len() {
+        return Err(checker.error("empty document"));
+    }
+    checker.value()?;
+    checker.skip_trivia()?;
+    if checker.position < text.len() {
+        return Err(checker.unexpected("after the document"));
+    }
+    Ok(())
+}
+
+struct Checker<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    position: usize,
+    strict: bool,
+}
+
+impl Checker<'_> {
+    fn error(&self, message: impl Into<String>) -> SyntaxError {
+        self.error_at(self.position, message)
+    }
+
+    fn error_at(&self, offset: usize, message: impl Into<String>) -> SyntaxError {
+        SyntaxError {
+            offset,
+            message: message.into(),
+        }
+    }
+
+    /// Error for the character at the current position
+    fn unexpected(&self, context: &str) -> SyntaxError {
+        match self.text[self.position..].chars().next() {
+            Some(c) => self.error(format!("unexpected `{}` {}", c, context)),
+            None => self.error(format!("unexpected end of input {}", context)),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn skip_trivia(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            let rest = &self.text[self.position..];
+            let trimmed = rest.trim_start();
+            self.position += rest.len() - trimmed.len();
+
+            if trimmed.starts_with("//") || trimmed.starts_with("/*") {
+                if self.strict {
+                    return Err(self.error("comments are not allowed in JSON"));
+                }
+                if trimmed.starts_with("//") {
+                    self.position += trimmed.find('\n').unwrap_or(trimmed.len());
+                } else {
+                    let end = trimmed[2..]
+                        .find("*/")
+                        .ok_or_else(|| self.error("unterminated comment"))?;
+                    self.position += end + 4;
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<(), SyntaxError> {
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string(),
+            Some(b'\'') if !self.strict => self.string(),
+            Some(b'-' | b'+' | b'.' | b'0'..=b'9') => self.number(),
+            Some(b'I' | b'N') if !self.strict => self.number(),
+            Some(b't' | b'f' | b'n') => self.literal(),
+            _ => Err(self.unexpected("where a value was expected")),
+        }
+    }
+
+    fn literal(&mut self) -> Result<(), SyntaxError> {
+        let rest = &self.text[self.position..];
+        for literal in ["true", "false", "null"] {
+            if rest.starts_with(literal) && !continues_identifier(&rest[literal.len()..]) {
+                self.position += literal.len();
+                return Ok(());
+            }
+        }
+        Err(self.unexpected("where a value was expected"))
+    }
+
+    fn object(&mut self) -> Result<(), SyntaxError> {
+        self.position += 1;
+        self.skip_trivia()?;
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(());
+        }
+
+        loop {
+            self.key()?;
+            self.skip_trivia()?;
+            if self.peek() != Some(b':') {
+                return Err(self.unexpected("where `:` was expected"));
+            }
+            self.position += 1;
+            self.skip_trivia()?;
+            self.value()?;
+            self.skip_trivia()?;
+
+            match self.peek() {
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(());
+                }
+                Some(b',') => {
+                    let comma = self.position;
+                    self.position += 1;
+                    self.skip_trivia()?;
+                    if self.peek() == Some(b'}') {
+                        if self.strict {
+                            return Err(self.error_at(comma, "trailing comma"));
+                        }
+                        self.position += 1;
+                        return Ok(());
+                    }
+                    if self.peek() == Some(b',') {
+                        return Err(self.error("duplicate comma"));
+                    }
+                }
+                _ => return Err(self.unexpected("where `,` or `}` was expected")),
+            }
+        }
+    }
+
+    fn key(&mut self) -> Result<(), SyntaxError> {
+        match self.peek() {
+            Some(b'"') => self.string(),
+            Some(b'\'') if !self.strict => self.string(),
+            Some(b',') => Err(self.error("duplicate comma")),
+            _ if !self.strict => {
+                let rest = &self.text[self.position..];
+                let length = rest
+                    .char_indices()
+                    .find(|&(i, c)| !is_identifier_char(c, i == 0))
+                    .map(|(i, _)| i)
+                    .unwrap_or(rest.len());
+                if length == 0 {
+                    return Err(self.unexpected("where a key was expected"));
+                }
+                self.position += length;
+                Ok(())
+            }
+            _ => Err(self.unexpected("where a quoted key was expected")),
+        }
+    }
+
+    fn array(&mut self) -> Result<(), SyntaxError> {
+        self.position += 1;
+        self.skip_trivia()?;
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(());
+        }
+
+        loop {
+            if self.peek() == Some(b',') {
+                return Err(self.error("duplicate comma"));
+            }
+            self.value()?;
+            self.skip_trivia()?;
+
+            match self.peek() {
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(());
+                }
+                Some(b',') => {
+                    let comma = self.position;
+                    self.position += 1;
+                    self.skip_trivia()?;
+                    if self.peek() == Some(b']') {
+                        if self.strict {
+                            return Err(self.error_at(comma, "trailing comma"));
+                        }
+                        self.position += 1;
+                        return Ok(());
+                    }
+                }
+                _ => return Err(self.unexpected("where `,` or `]` was expected")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<(), SyntaxError> {
+        let start = self.position;
+        let quote = self.bytes[start] as char;
+        let mut chars = self.text[start + 1..].char_indices();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                c if c == quote => {
+                    self.position = start + 1 + i + 1;
+                    return Ok(());
+                }
+                '\\' => {
+                    let escape_at = start + 1 + i;
+                    match chars.next() {
+                        Some((_, '"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't')) => {}
+                        Some((_, 'u')) => {
+                            for _ in 0..4 {
+                                if !chars.next().is_some_and(|(_, c)| c.is_ascii_hexdigit()) {
+                                    return Err(self.error_at(escape_at, "invalid unicode escape"));
+                                }
+                            }
+                        }
+                        Some(_) if !self.strict => {}
+                        Some(_) => return Err(self.error_at(escape_at, "invalid escape sequence")),
+                        None => break,
+                    }
+                }
+                '\n' | '\r' => {
+                    return Err(self.error_at(start + 1 + i, "unescaped line break in string"))
+                }
+                c if self.strict && c.is_control() => {
+                    return Err(
+                        self.error_at(start + 1 + i, "unescaped control character in string")
+                    )
+                }
+                _ => {}
+            }
+        }
+
+        Err(self.error_at(start, "unterminated string"))
+    }
+
+    fn number(&mut self) -> Result<(), SyntaxError> {
+        let start = self.position;
+        let rest = &self.text[start..];
+        let length = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')))
+            .unwrap_or(rest.len());
+        let token = &rest[..length];
+        self.position += length;
+
+        let valid = if self.strict {
+            is_json_number(token)
+        } else {
+            is_json5_number(token)
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(self.error_at(start, format!("invalid number `{}`", token)))
+        }
+    }
+}
+
+fn continues_identifier(rest: &str) -> bool {
+    rest.chars()
+        .next()
+        .is_some_and(|c| is_identifier_char(c, false))
+}
+
+fn is_identifier_char(c: char, first: bool) -> bool {
+    c == '_' || c == '$' || c.is_alphabetic() || (!first && c.is_alphanumeric())
+}
+
+fn is_json_number(token: &str) -> bool {
+    let digits = token.strip_prefix('-').unwrap_or(token);
+    let (mantissa, exponent) = match digits.find(['e', 'E']) {
+        Some(i) => (&digits[..i], Some(&digits[i + 1..])),
+        None => (digits, None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (mantissa, None),
+    };
+
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    all_digits(integer)
+        && (integer == "0" || !integer.starts_with('0'))
+        && fraction.is_none_or(all_digits)
+        && exponent.is_none_or(|e| all_digits(e.strip_prefix(['+', '-']).unwrap_or(e)))
+}
+
+fn is_json5_number(token: &str) -> bool {
+    let unsigned = token.strip_prefix(['+', '-']).unwrap_or(token);
+    if matches!(unsigned, "Infinity" | "NaN") {
+        return true;
+    }
+    if let Some(hex) = unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    {
+        return !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit());
+    }
+
+    // Leading and trailing decimal points are allowed: `.5`, `5.`
+    let normalized = match unsigned.find(['e', 'E']) {
+        Some(i) => format!("{}{}", pad_decimal(&unsigned[..i]), &unsigned[i..]),
+        None => pad_decimal(unsigned),
+    };
+    is_json_number(&normalized)
+}
+
+fn pad_decimal(mantissa: &str) -> String {
+    let mut padded = mantissa.to_string();
+    if padded.starts_with('.') {
+        padded.insert(0, '0');
+    }
+    if padded.ends_with('.') {
+        padded.push('0');
+    }
+    padded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json5(text: &str) -> Result<(), SyntaxError> {
+        check(text, &ValidateOptions::default())
+    }
+
+    fn json(text: &str) -> Result<(), SyntaxError> {
+        check(text, &ValidateOptions { strict_json: true })
+    }
+
+    #[test]
+    fn test_accepts_json5() {
+        json5("{a: 1, 'b': [0x1F, .5, 5., +Infinity, NaN,], // c\n /* d */ \"e\": null,}").unwrap();
+        json5("'multi\\\nline'").unwrap();
+        json(r#"{"a": [1, -2.5e+3, true, "xé\n"]}"#).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_invalid_documents() {
+        assert_eq!(json5("[1,,2]").unwrap_err().message, "duplicate comma");
+        assert_eq!(json5("{a: 1,,}").unwrap_err().offset, 6);
+        assert_eq!(
+            json5("{a: 1 b: 2}").unwrap_err().message,
+            "unexpected `b` where `,` or `}` was expected"
+        );
+        assert_eq!(json5("").unwrap_err().message, "empty document");
+        assert_eq!(json5("\"abc").unwrap_err().message, "unterminated string");
+        assert_eq!(json5("[01]").unwrap_err().message, "invalid number `01`");
+        assert_eq!(
+            json5("{} {}").unwrap_err().message,
+            "unexpected `{` after the document"
+        );
+    }
+
+    #[test]
+    fn test_strict_json() {
+        let error = json("[1, 2,\n]").unwrap_err();
+        assert_eq!(error.message, "trailing comma");
+        assert_eq!(error.offset, 5);
+        assert!(json("{a: 1}").is_err());
+        assert!(json("['a']").is_err());
+        assert!(json("// c\n1").is_err());
+        assert!(json("0x10").is_err());
+    }
+}
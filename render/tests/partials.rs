@@ -0,0 +1,82 @@
+use mustache_json5_render::{FileSystemResolver, MemoryResolver, MustacheJson5Renderer};
+use serde_json::json;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_render_with_memory_resolver() {
+    let mut renderer = MustacheJson5Renderer::new().unwrap();
+    renderer.set_resolver(MemoryResolver::from_iter([
+        (
+            "shared.header",
+            r#""version": {{version}}, {{> shared.meta}}"#,
+        ),
+        ("shared.meta", r#""meta": true"#),
+    ]));
+
+    let output = renderer
+        .render(r#"{ {{> shared.header}} }"#, &json!({"version": 3}))
+        .unwrap();
+    assert_eq!(output, r#"{ "version": 3, "meta": true }"#);
+}
+
+#[test]
+fn test_render_with_file_system_resolver() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir_all(dir.path().join("shared")).unwrap();
+    let header = dir.path().join("shared/header.mjson5");
+    fs::write(&header, r#""name": "{{name}}""#).unwrap();
+
+    let mut renderer = MustacheJson5Renderer::new().unwrap();
+    renderer.set_resolver(FileSystemResolver::new([dir.path()]));
+
+    let output = renderer
+        .render(
+            "{ {{> shared.header}}, {{> missing}} }",
+            &json!({"name": "x"}),
+        )
+        .unwrap();
+    assert_eq!(output, r#"{ "name": "x",  }"#);
+    assert_eq!(
+        renderer.partial_path("shared.header"),
+        Some(header.as_path())
+    );
+    assert!(renderer.partial("missing").is_none());
+}
+
+#[test]
+fn test_resolved_cycle_is_an_error() {
+    let mut renderer = MustacheJson5Renderer::new().unwrap();
+    renderer.set_resolver(MemoryResolver::from_iter([
+        ("a", "[{{> b}}]"),
+        ("b", "{{#x}}{{/x}}{{> a}}"),
+    ]));
+
+    let error = renderer.render("{{> a}}", &json!({})).unwrap_err();
+    assert!(error.to_string().contains("a -> b -> a"), "{}", error);
+}
+
+#[test]
+fn test_check_schema_follows_partials() {
+    let mut renderer = MustacheJson5Renderer::new().unwrap();
+    renderer.set_resolver(MemoryResolver::from_iter([(
+        "user",
+        r#"{"name": "{{nmae}}"}"#,
+    )]));
+
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "users": {"type": "array", "items": {"properties": {"name": {"type": "string"}}}}
+        }
+    });
+    let issues = renderer
+        .check_schema("[{{#users}}{{> user}},{{/users}}]", &schema)
+        .unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].partial.as_deref(), Some("user"));
+    assert_eq!(
+        issues[0].to_string(),
+        "line 1, column 11 of partial `user`: `nmae` is not defined in the schema"
+    );
+}
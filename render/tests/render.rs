@@ -0,0 +1,128 @@
+use mustache_json5_render::MustacheJson5Renderer;
+use pretty_assertions::assert_eq;
+use serde_json::json;
+
+#[test]
+fn test_render_config_template() {
+    let template = r#"{
+  // Generated service config
+  name: "{{service.name}}",
+  port: {{service.port}},
+  replicas: {{#production}}3{{/production}}{{^production}}1{{/production}},
+  endpoints: [
+    {{#each endpoints as |endpoint|}}
+    { path: "{{endpoint.path}}", public: {{endpoint.public}} },
+    {{/each}}
+  ],
+}
+"#;
+    let data = json!({
+        "service": {"name": "api", "port": 8080},
+        "production": true,
+        "endpoints": [
+            {"path": "/health", "public": true},
+            {"path": "/admin", "public": false}
+        ]
+    });
+
+    let output = MustacheJson5Renderer::new()
+        .unwrap()
+        .render(template, &data)
+        .unwrap();
+
+    // Tags are not treated as standalone lines, so their indentation stays
+    assert_eq!(
+        output,
+        "{
+  // Generated service config
+  name: \"api\",
+  port: 8080,
+  replicas: 3,
+  endpoints: [
+    \n    { path: \"/health\", public: true },
+    \n    { path: \"/admin\", public: false },
+    \n  ],
+}
+"
+    );
+}
+
+#[test]
+fn test_render_partials() {
+    let mut renderer = MustacheJson5Renderer::new().unwrap();
+    renderer
+        .add_partial("shared.header", r#""version": {{version}}"#)
+        .unwrap();
+
+    let output = renderer
+        .render(
+            r#"{ {{> shared.header}}, "name": "{{name}}" }"#,
+            &json!({"version": 2, "name": "x"}),
+        )
+        .unwrap();
+    assert_eq!(output, r#"{ "version": 2, "name": "x" }"#);
+
+    // Unknown partials render as nothing
+    let output = renderer.render("[{{> missing}}]", &json!({})).unwrap();
+    assert_eq!(output, "[]");
+}
+
+#[test]
+fn test_recursive_partials() {
+    let mut renderer = MustacheJson5Renderer::new().unwrap();
+    renderer
+        .add_partial("tree", "{{name}}[{{#children}}{{> tree}}{{/children}}]")
+        .unwrap();
+    let output = renderer
+        .render(
+            "{{> tree}}",
+            &json!({"name": "a", "children": [{"name": "b", "children": []}]}),
+        )
+        .unwrap();
+    assert_eq!(output, r#""a"["b"[]]"#);
+
+    // Recursion that never runs out of data hits the depth limit
+    renderer
+        .add_partial("forever", "[{{#x}}{{> forever}}{{/x}}]")
+        .unwrap();
+    let error = renderer
+        .render("{{> forever}}", &json!({"x": true}))
+        .unwrap_err();
+    assert!(error.to_string().contains("maximum nesting depth"));
+
+    // Recursion outside any section can never end
+    renderer.add_partial("loop", "[{{> loop}}]").unwrap();
+    let error = renderer.render("{{> loop}}", &json!({})).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("includes itself without an enclosing section: loop -> loop"));
+
+    // A broken partial the template never includes does not get in the way
+    assert_eq!(
+        renderer
+            .render("[{{> tree}}]", &json!({"name": "a"}))
+            .unwrap(),
+        r#"["a"[]]"#
+    );
+}
+
+#[test]
+fn test_mismatched_section_is_an_error() {
+    let error = MustacheJson5Renderer::new()
+        .unwrap()
+        .render("{{#a}}1{{/b}}", &json!({}))
+        .unwrap_err();
+    assert!(error.to_string().contains("Section `a` closed by `{{/b}}`"));
+}
+
+#[test]
+fn test_loop_variables() {
+    let output = MustacheJson5Renderer::new()
+        .unwrap()
+        .render(
+            "[{{#each items}}{{@index}}{{#if @first}}!{{/if}}{{#unless @last}},{{/unless}}{{/each}}]",
+            &json!({"items": ["a", "b", "c"]}),
+        )
+        .unwrap();
+    assert_eq!(output, "[0!,1,2]");
+}
@@ -0,0 +1,107 @@
+use mustache_json5_render::{MustacheJson5Renderer, ValidateOptions, ValidationError};
+use serde_json::json;
+
+fn validation_error(
+    renderer: &mut MustacheJson5Renderer,
+    template: &str,
+    data: serde_json::Value,
+    options: &ValidateOptions,
+) -> ValidationError {
+    renderer
+        .render_validated(template, &data, options)
+        .unwrap_err()
+        .downcast::<ValidationError>()
+        .expect("expected a validation error")
+}
+
+#[test]
+fn test_valid_output_is_returned() {
+    let mut renderer = MustacheJson5Renderer::new().unwrap();
+    let output = renderer
+        .render_validated(
+            "{\n  items: [{{#items}}{{.}}, {{/items}}],\n}\n",
+            &json!({"items": [1, 2]}),
+            &ValidateOptions::default(),
+        )
+        .unwrap();
+    assert_eq!(output, "{\n  items: [1, 2, ],\n}\n");
+}
+
+#[test]
+fn test_trailing_comma_is_blamed_on_section() {
+    let mut renderer = MustacheJson5Renderer::new().unwrap();
+    let template = r#"{
+  "name": "{{name}}",
+  "items": [
+    {{#items}}
+    "{{.}}",
+    {{/items}}
+  ]
+}
+"#;
+    let error = validation_error(
+        &mut renderer,
+        template,
+        json!({"name": "x", "items": ["a", "b"]}),
+        &ValidateOptions { strict_json: true },
+    );
+
+    assert_eq!(error.message, "trailing comma");
+    assert_eq!(
+        error.origin.as_deref(),
+        Some("produced by `{{#items}}` at line 4")
+    );
+    assert_eq!(
+        error.to_string(),
+        "trailing comma produced by `{{#items}}` at line 4 (rendered output line 7, column 8)"
+    );
+}
+
+#[test]
+fn test_error_in_variable_and_text() {
+    let mut renderer = MustacheJson5Renderer::new().unwrap();
+
+    let error = validation_error(
+        &mut renderer,
+        "{\n  a: {{{raw}}},\n}",
+        json!({"raw": "1 2"}),
+        &ValidateOptions::default(),
+    );
+    assert_eq!(
+        error.origin.as_deref(),
+        Some("produced by `{{{raw}}}` at line 2")
+    );
+
+    let error = validation_error(
+        &mut renderer,
+        "{\n  a: {{n}}\n  b: 2,\n}",
+        json!({"n": 1}),
+        &ValidateOptions::default(),
+    );
+    assert_eq!(
+        error.message,
+        "unexpected `b` where `,` or `}` was expected"
+    );
+    assert_eq!(
+        error.origin.as_deref(),
+        Some("at template line 3, column 3")
+    );
+}
+
+#[test]
+fn test_error_in_partial() {
+    let mut renderer = MustacheJson5Renderer::new().unwrap();
+    renderer.add_partial("entry", "{{#x}}1,,{{/x}}").unwrap();
+
+    let error = validation_error(
+        &mut renderer,
+        "[{{> entry}}]",
+        json!({"x": true}),
+        &ValidateOptions::default(),
+    );
+    assert_eq!(error.message, "duplicate comma");
+    assert_eq!(
+        error.origin.as_deref(),
+        Some("produced by `{{#x}}` at line 1 of partial `entry`")
+    );
+}